@@ -3,6 +3,10 @@
 SERVER_DIR="https://dnevest.com/execute/dnevest"
 CONTENT_TYPE="application/json"
 
+# The newspapers are created in a single batch, so a failure leaves the catalog untouched.
+# The first step of scenarios/init.json creates the same newspapers, keep them in sync.
+MESSAGES=()
+
 create_newspaper() {
   local signature="$1"
   local name="$2"
@@ -10,9 +14,7 @@ create_newspaper() {
   local end_year="$4"
   local weekly_schedule="$5"
 
-  curl -k -X POST "$SERVER_DIR" \
-    -H "Content-Type: $CONTENT_TYPE" \
-    -d "{\"CreateNewspaper\":{\"input\":{\"signature\":\"$signature\",\"name\":\"$name\",\"start_year\":$start_year,\"end_year\":$end_year,\"weekly_schedule\":$weekly_schedule}}}"
+  MESSAGES+=("{\"CreateNewspaper\":{\"input\":{\"signature\":\"$signature\",\"name\":\"$name\",\"start_year\":$start_year,\"end_year\":$end_year,\"weekly_schedule\":$weekly_schedule}}}")
 }
 
 create_newspaper "В1645" "Стършел" 1946 null "[false, false, false, false, true, false, false]"
@@ -28,4 +30,9 @@ create_newspaper "В1708" "Вечерни новини" 1952 1992 "[true, true,
 create_newspaper "В5499" "Стандарт" 1992 null "[true, true, true, true, true, true, true]"
 create_newspaper "В1905" "Поглед" 1966 1996 "[true, false, false, false, false, false, false]"
 create_newspaper "В1621" "Отечествен глас" 1960 1989 "[true, true, true, true, true, true, true]"
-create_newspaper "В1601" "Отечествен фронт" 1945 1989 "[false, true, true, true, true, true, true]"
\ No newline at end of file
+create_newspaper "В1601" "Отечествен фронт" 1945 1989 "[false, true, true, true, true, true, true]"
+
+BATCH=$(IFS=,; echo "{\"Batch\":[${MESSAGES[*]}]}")
+curl -k -X POST "$SERVER_DIR" \
+  -H "Content-Type: $CONTENT_TYPE" \
+  -d "$BATCH"
@@ -0,0 +1,7 @@
+//! Runs the messages of the contract natively, see `dnevest-cli --help`.
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    dnevest::cli::run(std::env::args())
+}
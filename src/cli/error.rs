@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    #[error("Cannot read {0}: {1}")]
+    Read(String, std::io::Error),
+
+    #[error("Invalid json in {0}: {1}")]
+    InvalidJson(String, serde_json::Error),
+
+    #[error("Cannot write the store {0}: {1}")]
+    Write(String, std::io::Error),
+
+    #[error("The store {0} is corrupted: {1}")]
+    CorruptedStore(String, serde_json::Error),
+
+    #[error("{0} must be a number of milliseconds, but is {1}")]
+    InvalidTime(&'static str, String),
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,157 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env, fs,
+    io::{self, ErrorKind},
+    ops::Bound,
+};
+
+use crate::{bindings::ByteArray, Storage};
+
+use super::error::{Error, Result};
+
+/// The file keeping the records, unless another one is given in this variable.
+pub(super) const STORE_VAR: &str = "DNEVEST_STORE";
+const DEFAULT_STORE: &str = "dnevest-store.json";
+
+thread_local! {
+    /// The opened store, shared by the storages created for a message, as on the host.
+    static STORE: RefCell<Store> = RefCell::new(Store::default());
+}
+
+#[derive(Default)]
+struct Store {
+    path: String,
+    records: BTreeMap<String, String>,
+    /// Whether any record has been persisted since the file was written.
+    changed: bool,
+}
+
+/// Keeps the records in a JSON file, as an object from the keys to the records.
+/// The records are kept in memory and written to the file with `save`,
+/// so that a failed write is reported instead of aborting the message half way.
+#[derive(Default)]
+pub(super) struct FileStorage;
+
+impl FileStorage {
+    /// A missing file is an empty store.
+    pub(super) fn open(path: &str) -> Result<()> {
+        let records = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|error| Error::CorruptedStore(path.to_string(), error))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(Error::Read(path.to_string(), error)),
+        };
+        STORE.with(|store| {
+            *store.borrow_mut() = Store {
+                path: path.to_string(),
+                records,
+                changed: false,
+            }
+        });
+        Ok(())
+    }
+
+    pub(super) fn path() -> String {
+        env::var(STORE_VAR).unwrap_or_else(|_| DEFAULT_STORE.to_string())
+    }
+
+    /// Writes the records to the file, if any of them has changed since the last write.
+    pub(super) fn save() -> Result<()> {
+        STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            if !store.changed {
+                return Ok(());
+            }
+            serde_json::to_string_pretty(&store.records)
+                .map_err(io::Error::from)
+                .and_then(|json| fs::write(&store.path, json))
+                .map_err(|error| Error::Write(store.path.clone(), error))
+                .map(|()| store.changed = false)
+        })
+    }
+}
+
+/// The records are JSON, so they are kept as text.
+impl Storage for FileStorage {
+    fn persist(&mut self, key: &str, value: &ByteArray) {
+        STORE.with(|store| {
+            let mut store = store.borrow_mut();
+            store
+                .records
+                .insert(key.to_string(), String::from_utf8_lossy(value).into_owned());
+            store.changed = true;
+        });
+    }
+
+    fn retrieve(&self, key: &str) -> Option<ByteArray> {
+        STORE.with(|store| {
+            store
+                .borrow()
+                .records
+                .get(key)
+                .map(|value| value.as_bytes().to_vec())
+        })
+    }
+
+    fn retrieve_range(&self, start: &str, end: &str) -> Vec<ByteArray> {
+        if start >= end {
+            return Vec::new();
+        }
+        STORE.with(|store| {
+            store
+                .borrow()
+                .records
+                .range::<str, _>((Bound::Included(start), Bound::Excluded(end)))
+                .map(|(_, value)| value.as_bytes().to_vec())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process};
+
+    use crate::{cli::error::Error, Storage};
+
+    use super::FileStorage;
+
+    #[test]
+    fn persisted_in_file() {
+        let path = env::temp_dir().join(format!("dnevest-store-{}.json", process::id()));
+        let path = path.to_str().unwrap();
+
+        FileStorage::open(path).unwrap();
+        let mut storage = FileStorage;
+        assert!(storage.retrieve("newspaper/В1612").is_none());
+        storage.persist("newspaper/В1612", &b"1612".to_vec());
+        storage.persist("newspaper/В4667", &b"4667".to_vec());
+        FileStorage.persist("order/1", &b"1".to_vec());
+        assert_eq!(storage.retrieve("order/1"), Some(b"1".to_vec()));
+        FileStorage::save().unwrap();
+
+        FileStorage::open(path).unwrap();
+        let reopened = FileStorage;
+        assert_eq!(reopened.retrieve("newspaper/В1612"), Some(b"1612".to_vec()));
+        assert_eq!(
+            reopened.retrieve_range("newspaper/", "newspaper0"),
+            vec![b"1612".to_vec(), b"4667".to_vec()]
+        );
+
+        fs::write(path, "{").unwrap();
+        assert!(FileStorage::open(path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failed_write() {
+        let path = env::temp_dir().join(format!("dnevest-missing-{}/store.json", process::id()));
+        let path = path.to_str().unwrap();
+
+        FileStorage::open(path).unwrap();
+        FileStorage::save().unwrap();
+        FileStorage.persist("order/1", &b"1".to_vec());
+        assert!(matches!(FileStorage::save(), Err(Error::Write(..))));
+    }
+}
@@ -0,0 +1,166 @@
+//! Runs the messages natively, against a file instead of the storage of the host,
+//! e.g. to replay `init.sh` or the examples of the readme offline.
+
+use std::{
+    fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use serde_json::Value;
+
+use crate::bindings::ByteArray;
+
+mod error;
+mod file_storage;
+mod system_clock;
+
+use error::{Error, Result};
+use file_storage::{FileStorage, STORE_VAR};
+use system_clock::{SystemClock, NOW_VAR};
+
+const STDIN: &str = "the standard input";
+
+enum Command {
+    Execute,
+    Query,
+}
+
+/// Runs every message read from the files, or from the standard input if there are none,
+/// and prints the events or the responses. Fails if any of the messages fails.
+pub fn run<I>(args: I) -> ExitCode
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().skip(1);
+    let command = match args.next().as_deref() {
+        Some("execute") => Command::Execute,
+        Some("query") => Command::Query,
+        _ => {
+            eprintln!("{}", usage());
+            return ExitCode::from(2);
+        }
+    };
+    let files: Vec<String> = args.collect();
+
+    let msgs = FileStorage::open(&FileStorage::path())
+        .and_then(|_| SystemClock::fixed())
+        .and_then(|_| self::read(&files));
+    match msgs {
+        Ok(msgs) => {
+            let mut failed = 0;
+            for msg in msgs {
+                if !self::dispatch(&command, msg) {
+                    failed += 1;
+                }
+                // written after every message, so that the executed ones are kept
+                if let Err(error) = FileStorage::save() {
+                    eprintln!("{}", error);
+                    return ExitCode::FAILURE;
+                }
+            }
+            if failed == 0 {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> String {
+    format!(
+        "Usage: dnevest-cli <execute|query> [FILE...]\n\
+         Runs the JSON messages read from the files, or from the standard input.\n\
+         The records are kept in the file named by {}, dnevest-store.json by default.\n\
+         The time is taken from the system, unless {} fixes it in milliseconds.",
+        STORE_VAR, NOW_VAR
+    )
+}
+
+/// Prints the events of an execution, one per line along with its id, or the response of a query.
+/// Returns whether the message succeeded.
+fn dispatch(command: &Command, msg: ByteArray) -> bool {
+    let res = match command {
+        Command::Execute => {
+            crate::execute::<FileStorage, SystemClock>(msg, &SystemClock).map(|events| {
+                events
+                    .into_iter()
+                    .map(|event| {
+                        format!("{} {}", event.id, String::from_utf8_lossy(&event.content))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        }
+        Command::Query => crate::query::<FileStorage, SystemClock>(msg, &SystemClock)
+            .map(|response| vec![String::from_utf8_lossy(&response).into_owned()]),
+    };
+    match res {
+        Ok(lines) => {
+            lines.iter().for_each(|line| println!("{}", line));
+            true
+        }
+        Err(error) => {
+            eprintln!("{}", String::from_utf8_lossy(&error));
+            false
+        }
+    }
+}
+
+fn read(files: &[String]) -> Result<Vec<ByteArray>> {
+    if files.is_empty() {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|error| Error::Read(STDIN.to_string(), error))
+            .and_then(|_| parse(STDIN, &text))
+    } else {
+        files.iter().try_fold(Vec::new(), |mut msgs, file| {
+            fs::read_to_string(file)
+                .map_err(|error| Error::Read(file.clone(), error))
+                .and_then(|text| parse(file, &text))
+                .map(|parsed| {
+                    msgs.extend(parsed);
+                    msgs
+                })
+        })
+    }
+}
+
+/// The messages follow one another, separated by whitespace, e.g. one on every line.
+fn parse(source: &str, text: &str) -> Result<Vec<ByteArray>> {
+    serde_json::Deserializer::from_str(text)
+        .into_iter::<Value>()
+        .map(|msg| {
+            msg.and_then(|msg| serde_json::to_vec(&msg))
+                .map_err(|error| Error::InvalidJson(source.to_string(), error))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn messages() {
+        let text = "\"MigrateAll\"\n{\"SpecifyMaxCards\":{\"max_number\":30}}\n\
+                    {\"AuditLog\": {\n  \"from_seq\": 1,\n  \"limit\": 10\n}}";
+        let msgs = super::parse("messages.json", text).unwrap();
+
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0], br#""MigrateAll""#);
+        assert_eq!(msgs[2], br#"{"AuditLog":{"from_seq":1,"limit":10}}"#);
+    }
+
+    #[test]
+    fn invalid_json() {
+        let error = super::parse("messages.json", "\"MigrateAll\" {\"SpecifyMaxCards\":")
+            .expect_err("expected the json to be invalid");
+        assert!(error
+            .to_string()
+            .starts_with("Invalid json in messages.json"));
+    }
+}
@@ -0,0 +1,40 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{bindings::component::dnevest::time::Clock, Time};
+
+use super::error::{Error, Result};
+
+/// Fixes the time in milliseconds, e.g. to replay the examples at the time they were taken.
+pub(super) const NOW_VAR: &str = "DNEVEST_NOW_MS";
+
+#[derive(Default)]
+pub(super) struct SystemClock;
+
+impl SystemClock {
+    /// The time fixed by the variable, if any.
+    pub(super) fn fixed() -> Result<Option<u64>> {
+        env::var(NOW_VAR)
+            .ok()
+            .map(|now| {
+                now.parse()
+                    .map_err(|_| Error::InvalidTime(NOW_VAR, now.clone()))
+            })
+            .transpose()
+    }
+}
+
+/// The time is checked once before running the messages, so it can be read here.
+impl Time for SystemClock {
+    fn now(&self) -> Clock {
+        let timestamp = Self::fixed().ok().flatten().unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| u64::try_from(elapsed.as_millis()).expect("the time fits in u64"))
+                .unwrap_or(0)
+        });
+        Clock { timestamp }
+    }
+}
@@ -6,6 +6,12 @@ use crate::bindings::ByteArray;
 pub(crate) enum Error {
     #[error("Invalid json in request: {0}")]
     InvalidRequest(serde_json::Error),
+
+    #[error("Unsupported version of the messages: {0}, the supported ones are 1 and 2")]
+    UnsupportedVersion(u64),
+
+    #[error("Problem while serialization: {0}")]
+    InvalidResponse(serde_json::Error),
 }
 
 impl Error {
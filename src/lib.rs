@@ -6,42 +6,56 @@ use bindings::{
     ByteArray, Guest,
 };
 
-use msgs::{ExecuteMsg, QueryMsg};
-
 #[allow(warnings)]
 #[rustfmt::skip]
 mod bindings;
+#[cfg(feature = "cli")]
+pub mod cli;
 mod errors;
 mod msgs;
 mod newspaper;
 mod order;
+mod repo;
 mod response;
+#[cfg(test)]
+mod scenario;
 mod services;
 
 struct Component;
 
 impl Guest for Component {
     fn execute(cmd: ByteArray) -> Result<Vec<bindings::Event>, Vec<u8>> {
-        msgs::deserialize_msg(cmd).and_then(|msg| match msg {
-            ExecuteMsg::CreateNewspaper { input } => services::create_newspaper::<Host>(input),
-            ExecuteMsg::AddFinalYear {
-                signature,
-                final_year,
-            } => services::add_final_year::<Host, Host>(signature, final_year),
-            ExecuteMsg::SpecifyMaxCards { max_number } => {
-                services::specify_max_cards::<Host>(max_number)
-            }
-            ExecuteMsg::CreateOrder { order } => services::create_order(order),
-        })
+        execute::<Host, Host>(cmd, &Host)
     }
 
     fn query(req: ByteArray) -> Result<ByteArray, ByteArray> {
-        msgs::deserialize_msg(req).and_then(|msg| match msg {
-            QueryMsg::NewspapersByDate { date } => services::newspapers_by_date::<Host, Host>(date),
-        })
+        query::<Host, Host>(req, &Host)
     }
 }
 
+/// Runs a message over the storage `S` and the clock, as the host runs it over its own.
+fn execute<S, T>(cmd: ByteArray, clock: &T) -> Result<Vec<bindings::Event>, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    msgs::deserialize_msg(cmd).and_then(|(msg, sent)| {
+        services::execute::<S, T>(msg, clock)
+            .and_then(|events| msgs::serialize_events(events, sent))
+    })
+}
+
+fn query<S, T>(req: ByteArray, clock: &T) -> Result<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    msgs::deserialize_msg(req).and_then(|(msg, sent)| {
+        services::query::<S, T>(msg, clock)
+            .and_then(|response| msgs::serialize_response(response, sent))
+    })
+}
+
 #[derive(Default)]
 struct Host;
 
@@ -67,12 +81,14 @@ impl Storage for Host {
     }
 }
 
+/// The clock of the host. Everything checked against the time, including the requests once
+/// they are deserialized, is given the clock, so that it can be checked at any instant.
 trait Time {
-    fn now() -> Clock;
+    fn now(&self) -> Clock;
 }
 
 impl Time for Host {
-    fn now() -> Clock {
+    fn now(&self) -> Clock {
         time::now()
     }
 }
@@ -1,19 +1,30 @@
-use serde::{de::DeserializeOwned, Deserialize};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
 use std::result::Result;
 
 use crate::{
-    bindings::ByteArray,
+    bindings::{self, ByteArray},
     errors::Error,
-    newspaper::{Date, Newspaper, Signature, Year},
-    order::OrderRequest,
-    Host,
+    newspaper::{Date, DateFormat, NewspaperRequest, Signature, Year},
+    order::{DeliverySchedule, OrderRequest, PhoneNumber, PriceList},
+    response::Published,
+    services::{CatalogFormat, Chunk, Fingerprinted, IdempotencyKey, ImportMode},
+    Host, Storage,
 };
 
+/// The orders are checked against the catalog and the settings of the storage they are
+/// executed on, the one of the host `S` unless they are in a batch.
 // #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Deserialize)]
-pub enum ExecuteMsg {
+#[derive(Deserialize, JsonSchema)]
+#[serde(bound = "")]
+#[schemars(bound = "", rename = "ExecuteMsg")]
+pub enum ExecuteMsg<S = Host>
+where
+    S: Storage + Default,
+{
     CreateNewspaper {
-        input: Newspaper,
+        input: NewspaperRequest,
     },
     AddFinalYear {
         signature: Signature,
@@ -22,28 +33,236 @@ pub enum ExecuteMsg {
     SpecifyMaxCards {
         max_number: u8,
     },
+    SpecifyDeliverySchedule {
+        schedule: DeliverySchedule,
+    },
+    /// The prices of the orders placed from now on, the placed ones keep their price.
+    SpecifyPriceList {
+        prices: PriceList,
+    },
     CreateOrder {
-        order: OrderRequest<Host, Host>,
+        order: Fingerprinted<OrderRequest<S>>,
+        #[serde(default)]
+        idempotency_key: Option<IdempotencyKey>,
+    },
+    SpecifyRetention {
+        days: u16,
+    },
+    /// The format the dates are written in from now on, every format is read either way.
+    SpecifyDateFormat {
+        format: DateFormat,
+    },
+    EraseCustomerData {
+        phone: PhoneNumber,
     },
+    /// Anonymizes the orders delivered before the retention period, e.g. sent daily by a scheduler.
+    EnforceRetention,
+    MigrateAll,
+    ImportNewspapers {
+        csv: String,
+        #[serde(default)]
+        mode: ImportMode,
+    },
+    /// Restores a chunk of a snapshot, e.g. of another deployment.
+    Restore {
+        chunk: Chunk,
+    },
+    /// Executes all messages or none of them.
+    Batch(Vec<ExecuteMsg<S>>),
 }
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub enum QueryMsg {
-    NewspapersByDate { date: Date },
+    NewspapersByDate {
+        date: Date,
+    },
+    NewspapersInRange {
+        from: Date,
+        to: Date,
+    },
+    /// The newspapers of every anniversary of the date, from the first up to `years`.
+    AnniversaryIssues {
+        date: Date,
+        years: Year,
+    },
+    /// The closest issues of a newspaper before and after the date, e.g. when it has none on it.
+    NearestIssues {
+        signature: Signature,
+        date: Date,
+        window_days: u16,
+    },
+    ExportCustomerData {
+        phone: PhoneNumber,
+    },
+    CheckIntegrity,
+    AuditLog {
+        from_seq: u64,
+        limit: u32,
+    },
+    ExportCatalog {
+        format: CatalogFormat,
+        #[serde(default)]
+        cursor: Option<Signature>,
+    },
+    Snapshot {
+        #[serde(default)]
+        cursor: Option<String>,
+    },
+    /// The JSON Schema of the messages, the events and the responses.
+    Schema,
+}
+
+/// The version of the shape of the messages, increased on every incompatible change of it.
+/// The messages sent without an envelope are read in version 1.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Copy)]
+pub(crate) enum Version {
+    V1,
+    V2,
+}
+
+impl Version {
+    fn number(self) -> u64 {
+        match self {
+            Version::V1 => 1,
+            Version::V2 => 2,
+        }
+    }
+
+    /// Why the version should not be used anymore, if it should not.
+    fn deprecation(self) -> Option<&'static str> {
+        match self {
+            Version::V1 => Some("Version 1 of the messages is deprecated, send them in version 2"),
+            Version::V2 => None,
+        }
+    }
+
+    /// Reads a message of this version into the internal one. The shape of the messages
+    /// has not changed since the envelope was introduced, so both versions read it as it is.
+    fn adapt<T: DeserializeOwned>(self, msg: &[u8]) -> Result<T, Error> {
+        match self {
+            Version::V1 | Version::V2 => serde_json::from_slice(msg).map_err(Error::InvalidRequest),
+        }
+    }
+}
+
+impl TryFrom<u64> for Version {
+    type Error = Error;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Version::V1),
+            2 => Ok(Version::V2),
+            _ => Err(Error::UnsupportedVersion(value)),
+        }
+    }
+}
+
+/// A message along with the version of its shape, e.g. {"v":2,"msg":"CheckIntegrity"}.
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Envelope<'a> {
+    /// The version of the shape of the message, 1 is deprecated.
+    #[schemars(range(min = 1, max = 2))]
+    v: u64,
+    #[serde(borrow)]
+    #[schemars(with = "serde_json::Value")]
+    msg: &'a RawValue,
+}
+
+/// The response to a query sent in an envelope, e.g. {"v":2,"response":[...]}.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct Response<'a> {
+    v: u64,
+    #[schemars(with = "serde_json::Value")]
+    response: &'a RawValue,
+    /// Why the version of the query should not be used anymore, if it should not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecation: Option<&'static str>,
+}
+
+/// How a message was sent: in which version, and whether in an envelope. The response
+/// keeps the shape of the message, so a message sent without one gets a bare response.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Copy)]
+pub(crate) struct Sent {
+    version: Version,
+    enveloped: bool,
+}
+
+/// Reads a message in an envelope, or without one in version 1.
+pub(crate) fn deserialize_msg<T: DeserializeOwned>(msg: ByteArray) -> Result<(T, Sent), ByteArray> {
+    match serde_json::from_slice::<Envelope<'_>>(&msg) {
+        Ok(envelope) => Version::try_from(envelope.v).and_then(|version| {
+            version.adapt(envelope.msg.get().as_bytes()).map(|msg| {
+                let sent = Sent {
+                    version,
+                    enveloped: true,
+                };
+                (msg, sent)
+            })
+        }),
+        Err(_) => Version::V1.adapt(&msg).map(|msg| {
+            let sent = Sent {
+                version: Version::V1,
+                enveloped: false,
+            };
+            (msg, sent)
+        }),
+    }
+    .map_err(|err| err.serialize())
 }
 
-pub(crate) fn deserialize_msg<T: DeserializeOwned>(msg: ByteArray) -> Result<T, ByteArray> {
-    serde_json::from_slice::<T>(&msg).map_err(|err| Error::InvalidRequest(err).serialize())
+/// Puts the response in an envelope of the version of the message, if it was sent in one,
+/// so that the clients still sending an old version are warned about it.
+pub(crate) fn serialize_response(response: ByteArray, sent: Sent) -> Result<ByteArray, ByteArray> {
+    if sent.enveloped {
+        serde_json::from_slice::<&RawValue>(&response)
+            .and_then(|response| {
+                serde_json::to_vec(&Response {
+                    v: sent.version.number(),
+                    response,
+                    deprecation: sent.version.deprecation(),
+                })
+            })
+            .map_err(|err| Error::InvalidResponse(err).serialize())
+    } else {
+        Ok(response)
+    }
+}
+
+/// Adds the warning of a deprecated version to the events of a message sent in it, with
+/// an envelope or without one, so that the clients still sending it are warned about it.
+pub(crate) fn serialize_events(
+    events: Vec<bindings::Event>,
+    sent: Sent,
+) -> Result<Vec<bindings::Event>, ByteArray> {
+    match sent.version.deprecation() {
+        Some(deprecation) => events
+            .into_iter()
+            .map(|event| {
+                Published::deprecate(&event.content, deprecation).map(|content| bindings::Event {
+                    id: event.id,
+                    content,
+                })
+            })
+            .collect::<serde_json::Result<Vec<bindings::Event>>>()
+            .map_err(|err| Error::InvalidResponse(err).serialize()),
+        None => Ok(events),
+    }
 }
 
 #[cfg(test)]
 mod test_deserialization {
     use std::fmt::Debug;
 
-    use crate::{bindings::ByteArray, newspaper::Date};
+    use crate::{
+        bindings::{self, ByteArray},
+        newspaper::Date,
+    };
 
-    use super::QueryMsg;
+    use super::{QueryMsg, Sent, Version};
 
     #[test]
     fn invalid_json() {
@@ -62,7 +281,92 @@ mod test_deserialization {
         };
         let res = super::deserialize_msg::<QueryMsg>(msg.into()).expect("deserialization failed");
 
-        assert_eq!(res, expected);
+        assert_eq!(res, (expected, bare()));
+    }
+
+    #[test]
+    fn envelope() {
+        let msg = r#"{"v":2,"msg":{"NewspapersByDate":{"date":"29-06-2024"}}}"#;
+        let expected = QueryMsg::NewspapersByDate {
+            date: Date::try_from("29-06-2024".to_string()).unwrap(),
+        };
+        let res = super::deserialize_msg::<QueryMsg>(msg.into()).expect("deserialization failed");
+        assert_eq!(res, (expected, enveloped(Version::V2)));
+
+        let res = super::deserialize_msg::<QueryMsg>(r#"{"v":1,"msg":"CheckIntegrity"}"#.into())
+            .expect("deserialization failed");
+        assert_eq!(res, (QueryMsg::CheckIntegrity, enveloped(Version::V1)));
+
+        assert_err(
+            super::deserialize_msg::<QueryMsg>(r#"{"v":3,"msg":"CheckIntegrity"}"#.into()),
+            "Unsupported version of the messages: 3",
+        );
+        assert_err(
+            super::deserialize_msg::<QueryMsg>(r#"{"v":65536,"msg":"CheckIntegrity"}"#.into()),
+            "Unsupported version of the messages: 65536",
+        );
+        assert_err(
+            super::deserialize_msg::<QueryMsg>(r#"{"v":2,"msg":{"date":"29-06-2024"}}"#.into()),
+            "Invalid json in request",
+        );
+    }
+
+    #[test]
+    fn response() {
+        let response = r#"[{"signature":"В1612","name":"Труд"}]"#.as_bytes().to_vec();
+
+        assert_eq!(
+            super::serialize_response(response.clone(), bare()).unwrap(),
+            response
+        );
+        assert_eq!(
+            super::serialize_response(response.clone(), enveloped(Version::V2)).unwrap(),
+            r#"{"v":2,"response":[{"signature":"В1612","name":"Труд"}]}"#.as_bytes()
+        );
+        assert_eq!(
+            super::serialize_response(response, enveloped(Version::V1)).unwrap(),
+            r#"{"v":1,"response":[{"signature":"В1612","name":"Труд"}],"deprecation":"Version 1 of the messages is deprecated, send them in version 2"}"#.as_bytes()
+        );
+    }
+
+    #[test]
+    fn events() {
+        let events = || {
+            vec![bindings::Event {
+                id: "dnevest_max_card".to_string(),
+                content: br#"{"version":2,"timestamp":1732880395000,"event":{"SpecifiedMaxCards":{"max_number":30}}}"#.to_vec(),
+            }]
+        };
+
+        assert_eq!(
+            super::serialize_events(events(), enveloped(Version::V2)).unwrap()[0].content,
+            events()[0].content
+        );
+
+        let deprecated = br#"{"version":2,"timestamp":1732880395000,"event":{"SpecifiedMaxCards":{"max_number":30}},"deprecation":"Version 1 of the messages is deprecated, send them in version 2"}"#;
+        assert_eq!(
+            super::serialize_events(events(), enveloped(Version::V1)).unwrap()[0].content,
+            deprecated
+        );
+        // the messages sent without an envelope are read in version 1, so they are warned too
+        assert_eq!(
+            super::serialize_events(events(), bare()).unwrap()[0].content,
+            deprecated
+        );
+    }
+
+    fn bare() -> Sent {
+        Sent {
+            version: Version::V1,
+            enveloped: false,
+        }
+    }
+
+    fn enveloped(version: Version) -> Sent {
+        Sent {
+            version,
+            enveloped: true,
+        }
     }
 
     fn assert_err<T: Debug>(r: Result<T, ByteArray>, msg: &str) {
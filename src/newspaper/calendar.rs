@@ -0,0 +1,401 @@
+use std::iter;
+
+use crate::{bindings::ByteArray, Storage, Time};
+
+use super::{
+    dto::{AnniversaryDTO, NearestIssuesDTO, NewspaperIssuesDTO, QueryNewspaperDTO},
+    error::{Error, Result},
+    newspaper::{self, Newspaper},
+    Date, DateFormat, Year,
+};
+
+/// The longest period searched for issues, so that a query reads a bounded number of days.
+const MAX_PERIOD_DAYS: usize = 366;
+
+/// The most anniversaries returned by a query.
+const MAX_ANNIVERSARIES: Year = 100;
+
+/// How far from an anniversary its nearest issues are searched, a week holds an issue
+/// of every newspaper published at least once a week.
+const NEAREST_ISSUE_DAYS: u16 = 7;
+
+/// The furthest from a date its nearest issues are searched, as far as the longest period.
+const MAX_WINDOW_DAYS: u16 = 366;
+
+/// The issues of every newspaper published within the period, both ends included.
+/// The newspapers without any issue in the period are left out.
+pub(crate) fn newspapers_in_range<S, T>(
+    from: Date,
+    to: Date,
+    format: DateFormat,
+    clock: &T,
+) -> Result<ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    let days = self::period(from, to)?;
+    let issues: Vec<NewspaperIssuesDTO> = newspaper::catalog::<S, T>(clock)
+        .into_iter()
+        .filter_map(|newspaper| {
+            let dates: Vec<Date> = days
+                .iter()
+                .filter(|day| newspaper.has_issue_on(day))
+                .copied()
+                .collect();
+            (!dates.is_empty()).then(|| {
+                NewspaperIssuesDTO::new(
+                    newspaper.identificator().to_string(),
+                    newspaper.name().to_string(),
+                    dates,
+                )
+            })
+        })
+        .collect();
+
+    format.to_vec(&issues).map_err(Error::SerializationFault)
+}
+
+/// The newspapers of every anniversary of the date up to the given number of years,
+/// e.g. of the 18th birthday. The anniversaries after today are left out.
+pub(crate) fn anniversary_issues<S, T>(
+    date: Date,
+    years: Year,
+    format: DateFormat,
+    clock: &T,
+) -> Result<ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    if !(1..=MAX_ANNIVERSARIES).contains(&years) {
+        return Err(Error::InvalidPeriod("years must be between 1 and 100"));
+    }
+    let today = Date::from_millis(clock.now().timestamp);
+    let catalog = newspaper::catalog::<S, T>(clock);
+    let anniversaries: Vec<AnniversaryDTO> = (1..=years)
+        .map(|years| (years, date.years_later(years)))
+        .take_while(|(_, anniversary)| *anniversary <= today)
+        .map(|(years, anniversary)| self::anniversary(&catalog, years, anniversary))
+        .collect();
+
+    format
+        .to_vec(&anniversaries)
+        .map_err(Error::SerializationFault)
+}
+
+fn anniversary(catalog: &[Newspaper], years: Year, date: Date) -> AnniversaryDTO {
+    let (published, others): (Vec<&Newspaper>, Vec<&Newspaper>) = catalog
+        .iter()
+        .partition(|newspaper| newspaper.has_issue_on(&date));
+    let newspapers = published
+        .into_iter()
+        .map(|newspaper| {
+            QueryNewspaperDTO::new(
+                newspaper.identificator().to_string(),
+                newspaper.name().to_string(),
+            )
+        })
+        .collect();
+    let nearest = others
+        .into_iter()
+        .filter_map(|newspaper| {
+            let dates = self::closest_issues(newspaper, date);
+            (!dates.is_empty()).then(|| {
+                NewspaperIssuesDTO::new(
+                    newspaper.identificator().to_string(),
+                    newspaper.name().to_string(),
+                    dates,
+                )
+            })
+        })
+        .collect();
+
+    AnniversaryDTO::new(years, date, newspapers, nearest)
+}
+
+/// The issues closest to the date, both the earlier and the later one when they are
+/// equally close. Empty if there is no issue within `NEAREST_ISSUE_DAYS`.
+fn closest_issues(newspaper: &Newspaper, date: Date) -> Vec<Date> {
+    (1..=NEAREST_ISSUE_DAYS)
+        .map(|days| {
+            [date.days_before(days), date.days_after(days)]
+                .into_iter()
+                .filter(|day| newspaper.has_issue_on(day))
+                .collect::<Vec<Date>>()
+        })
+        .find(|dates| !dates.is_empty())
+        .unwrap_or_default()
+}
+
+/// The closest issues of the newspaper before and after the date, at most `window_days` away.
+pub(crate) fn nearest_issues(
+    newspaper: &Newspaper,
+    date: Date,
+    window_days: u16,
+) -> Result<NearestIssuesDTO> {
+    if !(1..=MAX_WINDOW_DAYS).contains(&window_days) {
+        return Err(Error::InvalidPeriod(
+            "window_days must be between 1 and 366",
+        ));
+    }
+    let issue = |day: Date| newspaper.has_issue_on(&day).then_some(day);
+    let earlier = (1..=window_days).find_map(|days| issue(date.days_before(days)));
+    let later = (1..=window_days).find_map(|days| issue(date.days_after(days)));
+
+    Ok(NearestIssuesDTO::new(
+        newspaper.identificator().to_string(),
+        newspaper.name().to_string(),
+        date,
+        newspaper.has_issue_on(&date),
+        earlier,
+        later,
+    ))
+}
+
+/// Every day of the period, both ends included.
+fn period(from: Date, to: Date) -> Result<Vec<Date>> {
+    if from > to {
+        return Err(Error::InvalidPeriod("from cannot be after to"));
+    }
+    let days: Vec<Date> = iter::successors(Some(from), |day| (*day < to).then(|| day.next_day()))
+        .take(MAX_PERIOD_DAYS + 1)
+        .collect();
+    if days.len() > MAX_PERIOD_DAYS {
+        Err(Error::InvalidPeriod("it cannot be longer than 366 days"))
+    } else {
+        Ok(days)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::{
+        newspaper::{Date, DateFormat, Newspaper, Year},
+        services::{MockClock, MockHost},
+    };
+
+    #[test]
+    fn week() {
+        // from Monday 10.07.1989 to Sunday 16.07.1989
+        let issues = in_range(Date::new(10, 7, 1989), Date::new(16, 7, 1989));
+
+        assert_eq!(
+            issues,
+            json!([
+                {"signature": "В1612", "name": "Труд", "dates": [
+                    "10-07-1989", "11-07-1989", "12-07-1989", "13-07-1989",
+                    "14-07-1989", "15-07-1989", "16-07-1989"
+                ]},
+                {"signature": "В1616", "name": "Народен спор", "dates": ["10-07-1989", "13-07-1989", "15-07-1989"]},
+                {"signature": "В4667", "name": "Орбита", "dates": ["15-07-1989"]}
+            ])
+        );
+    }
+
+    #[test]
+    fn configured_format() {
+        let res = super::newspapers_in_range::<MockHost, MockClock>(
+            Date::new(15, 7, 1989),
+            Date::new(15, 7, 1989),
+            DateFormat::Dotted,
+            &MockClock::default(),
+        )
+        .expect("Failed to find the issues in the period");
+        let issues: Value = serde_json::from_slice(&res).unwrap();
+
+        assert_eq!(issues[0]["dates"], json!(["15.07.1989"]));
+    }
+
+    #[test]
+    fn end_of_publication() {
+        // Народен спорт was last published in 1989
+        let issues = in_range(Date::new(30, 12, 1989), Date::new(2, 1, 1990));
+        let signatures: Vec<&str> = issues
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|issues| issues["signature"].as_str())
+            .collect();
+
+        assert_eq!(signatures, vec!["В1612", "В1616", "В4667"]);
+        assert_eq!(issues[1]["dates"], json!(["30-12-1989"]));
+    }
+
+    #[test]
+    fn invalid_period() {
+        assert!(super::period(Date::new(2, 1, 1990), Date::new(1, 1, 1990))
+            .expect_err("expected the period to be reversed")
+            .to_string()
+            .contains("from cannot be after to"));
+        assert_eq!(
+            super::period(Date::new(1, 1, 2024), Date::new(31, 12, 2024))
+                .unwrap()
+                .len(),
+            366
+        );
+        assert!(super::period(Date::new(1, 1, 2023), Date::new(1, 1, 2024)).is_ok());
+        assert!(super::period(Date::new(1, 1, 2023), Date::new(2, 1, 2024)).is_err());
+    }
+
+    #[test]
+    fn anniversaries() {
+        // Monday 13.07.1970, its anniversaries are on Tuesday and on Thursday
+        let anniversaries = of_anniversaries(Date::new(13, 7, 1970), 2);
+
+        assert_eq!(
+            anniversaries,
+            json!([
+                {
+                    "years": 1,
+                    "date": "13-07-1971",
+                    "newspapers": [{"signature": "В1612", "name": "Труд"}],
+                    "nearest": [
+                        {"signature": "В1616", "name": "Народен спор", "dates": ["12-07-1971"]},
+                        {"signature": "В4667", "name": "Орбита", "dates": ["10-07-1971"]}
+                    ]
+                },
+                {
+                    "years": 2,
+                    "date": "13-07-1972",
+                    "newspapers": [
+                        {"signature": "В1612", "name": "Труд"},
+                        {"signature": "В1616", "name": "Народен спор"}
+                    ],
+                    "nearest": [{"signature": "В4667", "name": "Орбита", "dates": ["15-07-1972"]}]
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn equally_near_issues() {
+        // Sunday 16.07.1989, between the issues on Saturday and on Monday
+        let anniversaries = of_anniversaries(Date::new(16, 7, 1988), 1);
+
+        assert_eq!(
+            anniversaries[0]["nearest"][0],
+            json!({"signature": "В1616", "name": "Народен спор", "dates": ["15-07-1989", "17-07-1989"]})
+        );
+    }
+
+    #[test]
+    fn anniversaries_until_today() {
+        // today is 29.11.2024
+        let anniversaries = of_anniversaries(Date::new(1, 1, 2020), 10);
+        let dates: Vec<&str> = anniversaries
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|anniversary| anniversary["date"].as_str())
+            .collect();
+
+        assert_eq!(
+            dates,
+            vec!["01-01-2021", "01-01-2022", "01-01-2023", "01-01-2024"]
+        );
+    }
+
+    #[test]
+    fn leap_day() {
+        let anniversaries = of_anniversaries(Date::new(29, 2, 2020), 4);
+
+        assert_eq!(anniversaries[0]["date"], json!("28-02-2021"));
+        assert_eq!(anniversaries[3]["date"], json!("29-02-2024"));
+    }
+
+    #[test]
+    fn invalid_years() {
+        for years in [0, 101] {
+            assert!(super::anniversary_issues::<MockHost, MockClock>(
+                Date::new(1, 1, 2000),
+                years,
+                DateFormat::default(),
+                &MockClock::default()
+            )
+            .expect_err("expected the years to be invalid")
+            .to_string()
+            .contains("years must be between 1 and 100"));
+        }
+    }
+
+    #[test]
+    fn nearest_issues() {
+        // Стършел is published on Fridays, Tuesday 13.08.2024 is between 09.08 and 16.08
+        let nearest = nearest_within(Date::new(13, 8, 2024), 7);
+        assert_eq!(
+            nearest,
+            json!({
+                "signature": "В1645",
+                "name": "Стършел",
+                "date": "13-08-2024",
+                "published": false,
+                "earlier": "09-08-2024",
+                "later": "16-08-2024"
+            })
+        );
+
+        let nearest = nearest_within(Date::new(13, 8, 2024), 3);
+        assert_eq!(
+            (nearest["earlier"].clone(), nearest["later"].clone()),
+            (json!(null), json!("16-08-2024"))
+        );
+
+        let nearest = nearest_within(Date::new(16, 8, 2024), 7);
+        assert_eq!(nearest["published"], json!(true));
+        assert_eq!(nearest["earlier"], json!("09-08-2024"));
+    }
+
+    #[test]
+    fn invalid_window() {
+        for window_days in [0, 367] {
+            assert!(
+                super::nearest_issues(&weekly(), Date::new(13, 8, 2024), window_days)
+                    .err()
+                    .expect("expected the window to be invalid")
+                    .to_string()
+                    .contains("window_days must be between 1 and 366")
+            );
+        }
+    }
+
+    fn nearest_within(date: Date, window_days: u16) -> Value {
+        let nearest = super::nearest_issues(&weekly(), date, window_days)
+            .expect("Failed to find the nearest issues");
+        serde_json::to_value(nearest).unwrap()
+    }
+
+    fn weekly() -> Newspaper {
+        Newspaper::new_unchecked(
+            "В1645",
+            "Стършел",
+            1946,
+            None,
+            [false, false, false, false, true, false, false],
+        )
+    }
+
+    fn of_anniversaries(date: Date, years: Year) -> Value {
+        let res = super::anniversary_issues::<MockHost, MockClock>(
+            date,
+            years,
+            DateFormat::default(),
+            &MockClock::default(),
+        )
+        .expect("Failed to find the issues of the anniversaries");
+        serde_json::from_slice(&res).unwrap()
+    }
+
+    fn in_range(from: Date, to: Date) -> Value {
+        let res = super::newspapers_in_range::<MockHost, MockClock>(
+            from,
+            to,
+            DateFormat::default(),
+            &MockClock::default(),
+        )
+        .expect("Failed to find the issues in the period");
+        serde_json::from_slice(&res).unwrap()
+    }
+}
@@ -0,0 +1,284 @@
+use super::{
+    error::{Error, Result},
+    Signature, UncheckedNewspaper, WeeklyFrequency, Year,
+};
+
+/// signature, name, start_year, end_year and a column for each day from Monday to Sunday
+const COLUMNS: usize = 11;
+
+/// The short and the full names of the days, from Monday to Sunday.
+const DAYS: [&[&str]; 7] = [
+    &["пн", "понеделник"],
+    &["вт", "вторник"],
+    &["ср", "сряда"],
+    &["чт", "чв", "четвъртък"],
+    &["пт", "петък"],
+    &["сб", "събота"],
+    &["нд", "неделя"],
+];
+
+/// Parses the rows of the catalog, as exported from the spreadsheet, along with their numbers.
+/// The columns are separated by commas, or by semicolons if the first line contains any.
+/// The first row is skipped if it is a header, i.e. its start year is not a number.
+pub(crate) fn parse(csv: &str) -> Result<Vec<(usize, Result<UncheckedNewspaper>)>> {
+    let separator = match csv.lines().next() {
+        Some(line) if line.contains(';') => ';',
+        _ => ',',
+    };
+    records(csv, separator).map(|records| {
+        records
+            .into_iter()
+            .zip(1..)
+            .filter(|(fields, _)| !fields.iter().all(|field| field.trim().is_empty()))
+            .filter(|(fields, row)| {
+                *row != 1
+                    || fields
+                        .get(2)
+                        .is_some_and(|year| year.trim().parse::<Year>().is_ok())
+            })
+            .map(|(fields, row)| (row, newspaper(fields)))
+            .collect()
+    })
+}
+
+fn newspaper(fields: Vec<String>) -> Result<UncheckedNewspaper> {
+    let fields: Vec<&str> = fields.iter().map(|field| field.trim()).collect();
+    if fields.len() != COLUMNS {
+        return Err(Error::ColumnCount(fields.len()));
+    }
+
+    let signature = Signature::try_from(fields[0].to_string())?;
+    let start_year = year(fields[2])?;
+    let end_year = match fields[3] {
+        "" | "-" => None,
+        end_year => Some(year(end_year)?),
+    };
+    let mut weekly_schedule = [false; 7];
+    for ((day, field), names) in weekly_schedule.iter_mut().zip(&fields[4..]).zip(DAYS) {
+        *day = published(field, names)?;
+    }
+
+    Ok(UncheckedNewspaper::new(
+        signature,
+        fields[1].to_string(),
+        start_year,
+        end_year,
+        WeeklyFrequency::new(weekly_schedule),
+    ))
+}
+
+fn year(field: &str) -> Result<Year> {
+    field
+        .parse()
+        .map_err(|_| Error::YearParsing(field.to_string()))
+}
+
+/// A day is marked with 1 or its name, and left empty or marked with 0 or '-' otherwise.
+fn published(field: &str, names: &[&str]) -> Result<bool> {
+    let field = field.to_lowercase();
+    match field.as_str() {
+        "1" => Ok(true),
+        "" | "0" | "-" => Ok(false),
+        name if names.contains(&name) => Ok(true),
+        _ => Err(Error::ScheduleDay(field)),
+    }
+}
+
+/// The header of the columns, with the short names of the days as in the spreadsheet.
+pub(crate) fn header() -> String {
+    [
+        "Сигнатура",
+        "Вестник",
+        "Начало на издаване",
+        "Край на издаване",
+    ]
+    .into_iter()
+    .chain(DAYS.iter().map(|names| names[0]))
+    .map(capitalize)
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// A row in the layout of the import, with the days marked by 1 or 0.
+pub(crate) fn row(newspaper: &UncheckedNewspaper) -> String {
+    let end_year = newspaper.end_year().map(|year| year.to_string());
+    let days = (0..DAYS.len()).map(|day| {
+        if newspaper.published_on(day) {
+            "1"
+        } else {
+            "0"
+        }
+    });
+    [
+        newspaper.signature().to_string(),
+        quote(newspaper.name()),
+        newspaper.start_year().to_string(),
+        end_year.unwrap_or_default(),
+    ]
+    .into_iter()
+    .chain(days.map(str::to_string))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Only the fields containing separators, line breaks or quotes are quoted.
+fn quote(field: &str) -> String {
+    if field.contains([',', ';', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits the text into records of fields. A field in double quotes may contain separators,
+/// line breaks and double quotes, which are escaped by doubling them.
+fn records(csv: &str, separator: char) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            c if quoted => field.push(c),
+            c if c == separator => fields.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut fields));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::UnterminatedQuote);
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push(fields);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{newspaper::Newspaper, services::MockClock, Time};
+
+    use super::Result;
+
+    #[test]
+    fn spreadsheet_export() {
+        let csv = "Сигнатура;Вестник;Начало на издаване;Край на издаване;Пн;Вт;Ср;Чв;Пт;Сб;Нд\r\n\
+                   В1612;Труд;1946;-;1;1;1;1;1;1;1\r\n\
+                   В4667;Орбита;1969;1991;0;0;0;0;0;1;0\r\n";
+        let rows = super::parse(csv).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(
+            checked(rows.into_iter().map(|(_, row)| row).collect()),
+            vec![
+                Newspaper::new_unchecked("В1612", "Труд", 1946, None, [true; 7]),
+                Newspaper::new_unchecked(
+                    "В4667",
+                    "Орбита",
+                    1969,
+                    Some(1991),
+                    [false, false, false, false, false, true, false]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn day_names_and_quotes() {
+        let csv = "В1616,\"Народен спорт, \"\"София\"\"\",1944,1989,Пн,,,Четвъртък,0,сб,\n";
+        let rows = super::parse(csv).unwrap();
+
+        assert_eq!(
+            checked(rows.into_iter().map(|(_, row)| row).collect()),
+            vec![Newspaper::new_unchecked(
+                "В1616",
+                "Народен спорт, \"София\"",
+                1944,
+                Some(1989),
+                [true, false, false, true, false, true, false]
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_rows() {
+        let csv = "signature,name,start_year,end_year,mon,tue,wed,thu,fri,sat,sun\n\
+                   B1612,Труд,1946,,1,1,1,1,1,1,1\n\
+                   В1612,Труд,1946\n\
+                   В1612,Труд,хиляда,,1,1,1,1,1,1,1\n\
+                   В1612,Труд,1946,,1,1,1,1,1,1,да\n\
+                   \n\
+                   В1612,Труд,1946,,1,1,1,1,1,1,1";
+        let rows = super::parse(csv).unwrap();
+
+        let errors: Vec<(usize, String)> = rows
+            .into_iter()
+            .map(|(row, res)| (row, res.err().map(|e| e.to_string()).unwrap_or_default()))
+            .collect();
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].1.contains("Signature does not match"));
+        assert!(errors[1].1.contains("Expected 11 columns, but found 3"));
+        assert!(errors[2].1.contains("хиляда"));
+        assert!(errors[3].1.contains("да"));
+        assert_eq!(errors[4], (7, String::new()));
+    }
+
+    #[test]
+    fn export_and_import() {
+        let csv = "Сигнатура,Вестник,Начало на издаване,Край на издаване,Пн,Вт,Ср,Чт,Пт,Сб,Нд\n\
+                   В1616,\"Народен спорт, \"\"София\"\"\",1944,1989,1,0,0,1,0,1,0\n\
+                   В1612,Труд,1946,,1,1,1,1,1,1,1";
+        let rows = super::parse(csv).unwrap();
+        let exported: Vec<String> = rows
+            .iter()
+            .map(|(_, row)| super::row(row.as_ref().unwrap()))
+            .collect();
+
+        assert_eq!(
+            std::iter::once(super::header())
+                .chain(exported)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            csv
+        );
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert!(super::parse("В1612,\"Труд,1946").is_err());
+    }
+
+    fn checked(rows: Vec<Result<super::UncheckedNewspaper>>) -> Vec<Newspaper> {
+        rows.into_iter()
+            .map(|row| {
+                row.unwrap()
+                    .into_checked(MockClock::default().now())
+                    .unwrap()
+            })
+            .collect()
+    }
+}
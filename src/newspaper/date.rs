@@ -1,32 +1,203 @@
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
 
-use std::result::Result as StdResult;
+use std::{ops::RangeInclusive, result::Result as StdResult};
 
-use chrono::{Datelike, NaiveDate, Weekday};
+use chrono::{DateTime, Datelike, Days, NaiveDate, Weekday};
+
+use crate::bindings::ByteArray;
 
 use super::{
     error::{Error, Result},
+    written::Written,
     Year,
 };
 
-const FORMAT: &str = "%d-%m-%Y";
+/// The formats in the regular expressions of JSON Schema, e.g. 16-08-2024, 2024-08-16 or 16.08.2024.
+const PATTERN: &str = r"^(\d{1,2}-\d{1,2}-\d{4}|\d{4}-\d{1,2}-\d{1,2}|\d{1,2}\.\d{1,2}\.\d{4})$";
+
+/// The years a date may fall in, the ones written with four digits.
+const YEARS: RangeInclusive<i32> = 1..=9999;
+
+/// The name the dates are serialized under, so that they are told apart from the other text.
+pub(super) const DATE: &str = "Date";
+
+/// The formats of the dates in text, in the order they are tried. The records are always
+/// written in the default one, the responses and the events in the configured one.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum DateFormat {
+    /// 16-08-2024
+    #[default]
+    DayMonthYear,
+    /// 2024-08-16, as sent by the date inputs of the browsers
+    Iso,
+    /// 16.08.2024, as the dates are written in Bulgarian
+    Dotted,
+}
+
+impl DateFormat {
+    const ALL: [DateFormat; 3] = [
+        DateFormat::DayMonthYear,
+        DateFormat::Iso,
+        DateFormat::Dotted,
+    ];
+
+    /// Serializes a response or an event with its dates in this format.
+    pub(crate) fn to_vec<V>(self, value: &V) -> serde_json::Result<ByteArray>
+    where
+        V: Serialize + ?Sized,
+    {
+        serde_json::to_vec(&Written::new(value, self))
+    }
+
+    /// Writes again a date written in the default format.
+    pub(super) fn rewrite(self, date: &str) -> Option<String> {
+        NaiveDate::parse_from_str(date, DateFormat::DayMonthYear.chrono())
+            .ok()
+            .map(|parsed| self.write(parsed))
+    }
+
+    fn chrono(self) -> &'static str {
+        match self {
+            DateFormat::DayMonthYear => "%d-%m-%Y",
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::Dotted => "%d.%m.%Y",
+        }
+    }
+
+    fn write(self, date: NaiveDate) -> String {
+        let (day, month, year) = (date.day(), date.month(), date.year());
+        match self {
+            DateFormat::DayMonthYear => format!("{:02}-{:02}-{:04}", day, month, year),
+            DateFormat::Iso => format!("{:04}-{:02}-{:02}", year, month, day),
+            DateFormat::Dotted => format!("{:02}.{:02}.{:04}", day, month, year),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DateFormat::DayMonthYear => "dd-mm-yyyy",
+            DateFormat::Iso => "yyyy-mm-dd",
+            DateFormat::Dotted => "dd.mm.yyyy",
+        }
+    }
+}
 
-#[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Deserialize, Clone)]
-#[serde(try_from = "String")]
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
+#[serde(try_from = "UncheckedDate", into = "String")]
 pub(crate) struct Date(NaiveDate);
 
+/// A date as the clients send it, either in text in any of the formats or by its parts.
+#[derive(Deserialize, JsonSchema)]
+#[serde(
+    untagged,
+    expecting = "a date as dd-mm-yyyy, yyyy-mm-dd, dd.mm.yyyy or {day, month, year}"
+)]
+#[schemars(rename = "Date")]
+enum UncheckedDate {
+    Text(#[schemars(extend("pattern" = PATTERN))] String),
+    Parts {
+        day: u32,
+        month: u32,
+        #[schemars(range(min = 1, max = 9999))]
+        year: i32,
+    },
+}
+
 impl Date {
     pub(super) fn try_new(date: &str) -> Result<Self> {
-        NaiveDate::parse_from_str(date, FORMAT)
-            .map_err(Error::DateParsing)
-            .map(Date)
+        DateFormat::ALL
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(date, format.chrono()).ok())
+            .ok_or_else(|| {
+                let tried: Vec<&str> = DateFormat::ALL.iter().map(|format| format.name()).collect();
+                Error::DateParsing(format!("{:?}, tried {}", date, tried.join(", ")))
+            })
+            .and_then(|parsed| Self::within_years(parsed, || format!("{:?}", date)))
+    }
+
+    fn from_parts(day: u32, month: u32, year: i32) -> Result<Self> {
+        let parts = || format!("{{day: {}, month: {}, year: {}}}", day, month, year);
+        NaiveDate::from_ymd_opt(year, month, day)
+            .ok_or_else(|| Error::DateParsing(format!("{}, there is no such day", parts())))
+            .and_then(|parsed| Self::within_years(parsed, parts))
+    }
+
+    fn within_years(date: NaiveDate, text: impl FnOnce() -> String) -> Result<Self> {
+        if YEARS.contains(&date.year()) {
+            Ok(Self(date))
+        } else {
+            Err(Error::DateParsing(format!(
+                "{}, the year must be from {} to {}",
+                text(),
+                YEARS.start(),
+                YEARS.end()
+            )))
+        }
     }
 
     pub(super) fn day_of_week(&self) -> Weekday {
         self.0.weekday()
     }
 
+    /// The index of the day in the weekly schedules, from 0 on Monday.
+    pub(super) fn day_index(&self) -> usize {
+        self.day_of_week().num_days_from_monday() as usize
+    }
+
+    pub(crate) fn is_weekend(&self) -> bool {
+        matches!(self.0.weekday(), Weekday::Sat | Weekday::Sun)
+    }
+
+    pub(crate) fn next_day(&self) -> Self {
+        Self(
+            self.0
+                .succ_opt()
+                .expect("Date must be before the end of time"),
+        )
+    }
+
+    pub(crate) fn days_before(&self, days: u16) -> Self {
+        Self(
+            self.0
+                .checked_sub_days(Days::new(u64::from(days)))
+                .expect("Date must be after the beginning of time"),
+        )
+    }
+
+    pub(super) fn days_after(&self, days: u16) -> Self {
+        Self(
+            self.0
+                .checked_add_days(Days::new(u64::from(days)))
+                .expect("Date must be before the end of time"),
+        )
+    }
+
+    /// The same day and month the given number of years later, 28 February for
+    /// a 29 February in a common year.
+    pub(super) fn years_later(&self, years: Year) -> Self {
+        let year = self.0.year() + i32::from(years);
+        Self(
+            self.0
+                .with_year(year)
+                .or_else(|| NaiveDate::from_ymd_opt(year, 2, 28))
+                .expect("Date must be before the end of time"),
+        )
+    }
+
+    /// The UTC date of a timestamp in milliseconds, as returned by the host clock.
+    pub(crate) fn from_millis(millis: u64) -> Self {
+        Self(
+            DateTime::from_timestamp_millis(
+                i64::try_from(millis).expect("u64 value is too large for i64"),
+            )
+            .expect("invalid timestamp")
+            .date_naive(),
+        )
+    }
+
     pub(super) fn year(&self) -> Year {
         self.0
             .year()
@@ -43,6 +214,28 @@ impl Date {
     }
 }
 
+impl From<NaiveDate> for Date {
+    fn from(value: NaiveDate) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Date> for String {
+    fn from(value: Date) -> Self {
+        DateFormat::DayMonthYear.write(value.0)
+    }
+}
+
+/// Written in the default format, under its own name so that a response can write it in another.
+impl Serialize for Date {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(DATE, &String::from(*self))
+    }
+}
+
 impl TryFrom<String> for Date {
     type Error = Error;
 
@@ -51,6 +244,17 @@ impl TryFrom<String> for Date {
     }
 }
 
+impl TryFrom<UncheckedDate> for Date {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedDate) -> StdResult<Self, Self::Error> {
+        match unchecked {
+            UncheckedDate::Text(text) => Self::try_new(&text),
+            UncheckedDate::Parts { day, month, year } => Self::from_parts(day, month, year),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_parse {
     use super::{Date, Result};
@@ -80,6 +284,60 @@ mod test_parse {
         assert_err(yyyy_mm_dd, msg);
     }
 
+    #[test]
+    fn other_formats() {
+        assert_eq!(Date::try_new("2024-02-29").unwrap(), Date::new(29, 2, 2024));
+        assert_eq!(Date::try_new("29.02.2024").unwrap(), Date::new(29, 2, 2024));
+        assert_eq!(Date::try_new("1.3.2024").unwrap(), Date::new(1, 3, 2024));
+        assert_err(Date::try_new("2026-02-29"), "Error parsing date");
+    }
+
+    #[test]
+    fn parts() {
+        let date: Date = serde_json::from_str(r#"{"day":29,"month":2,"year":2024}"#).unwrap();
+        assert_eq!(date, Date::new(29, 2, 2024));
+
+        let error = serde_json::from_str::<Date>(r#"{"day":29,"month":2,"year":2026}"#)
+            .expect_err("expected the day not to exist");
+        assert!(error
+            .to_string()
+            .contains("{day: 29, month: 2, year: 2026}, there is no such day"));
+    }
+
+    #[test]
+    fn years() {
+        assert_eq!(
+            Date::try_new("31-12-9999").unwrap(),
+            Date::new(31, 12, 9999)
+        );
+        assert_eq!(Date::try_new("0001-01-01").unwrap(), Date::new(1, 1, 1));
+        for date in ["01-01-0000", "+70000-01-01", "-0005-01-01"] {
+            assert_err(Date::try_new(date), "the year must be from 1 to 9999");
+        }
+
+        let error = serde_json::from_str::<Date>(r#"{"day":1,"month":1,"year":70000}"#)
+            .expect_err("expected the year to be too late");
+        assert!(error
+            .to_string()
+            .contains("{day: 1, month: 1, year: 70000}, the year must be from 1 to 9999"));
+        assert!(serde_json::from_str::<Date>(r#"{"day":1,"month":1,"year":-5}"#).is_err());
+    }
+
+    #[test]
+    fn tried_formats() {
+        let error = serde_json::from_str::<Date>(r#""13/06/2022""#)
+            .expect_err("expected the format to be unknown");
+        assert!(error.to_string().contains(
+            r#"Error parsing date "13/06/2022", tried dd-mm-yyyy, yyyy-mm-dd, dd.mm.yyyy"#
+        ));
+
+        let error =
+            serde_json::from_str::<Date>("20240816").expect_err("expected a text or the parts");
+        assert!(error
+            .to_string()
+            .contains("a date as dd-mm-yyyy, yyyy-mm-dd, dd.mm.yyyy or {day, month, year}"));
+    }
+
     fn assert_err(r: Result<Date>, msg: &str) {
         assert!(r.expect_err("expected an error").to_string().contains(msg))
     }
@@ -88,8 +346,9 @@ mod test_parse {
 #[cfg(test)]
 mod test {
     use chrono::Weekday;
+    use serde::Serialize;
 
-    use crate::newspaper::Date;
+    use crate::newspaper::{Date, DateFormat};
 
     #[test]
     fn test_various_days_of_week() {
@@ -102,4 +361,79 @@ mod test {
         let date3 = Date::try_new("25-12-2023").unwrap();
         assert_eq!(date3.day_of_week(), Weekday::Mon);
     }
+
+    #[test]
+    fn serialize() {
+        let serialized = serde_json::to_string(&Date::new(5, 7, 1987)).unwrap();
+        assert_eq!(serialized, r#""05-07-1987""#);
+    }
+
+    #[test]
+    fn configured_format() {
+        // only the dates are written again, not the text that looks like one
+        #[derive(Serialize)]
+        struct Dated {
+            date: Date,
+            text: &'static str,
+        }
+        let dated = Dated {
+            date: Date::new(5, 7, 1987),
+            text: "05-07-1987",
+        };
+        let written =
+            |format: DateFormat| String::from_utf8(format.to_vec(&dated).unwrap()).unwrap();
+
+        assert_eq!(
+            written(DateFormat::Iso),
+            r#"{"date":"1987-07-05","text":"05-07-1987"}"#
+        );
+        assert_eq!(
+            written(DateFormat::Dotted),
+            r#"{"date":"05.07.1987","text":"05-07-1987"}"#
+        );
+        assert_eq!(
+            written(DateFormat::DayMonthYear),
+            r#"{"date":"05-07-1987","text":"05-07-1987"}"#
+        );
+    }
+
+    #[test]
+    fn write_every_format() {
+        let date = chrono::NaiveDate::from_ymd_opt(1987, 7, 5).unwrap();
+        let written: Vec<Date> = DateFormat::ALL
+            .iter()
+            .map(|format| Date::try_new(&format.write(date)).unwrap())
+            .collect();
+        assert!(written.iter().all(|read| *read == Date::new(5, 7, 1987)));
+    }
+
+    #[test]
+    fn from_millis() {
+        assert_eq!(Date::from_millis(1732880395000), Date::new(29, 11, 2024));
+        assert_eq!(Date::new(1, 3, 2024).days_before(1), Date::new(29, 2, 2024));
+        assert_eq!(Date::new(28, 2, 2024).days_after(2), Date::new(1, 3, 2024));
+    }
+
+    #[test]
+    fn years_later() {
+        assert_eq!(
+            Date::new(13, 6, 2006).years_later(18),
+            Date::new(13, 6, 2024)
+        );
+        assert_eq!(
+            Date::new(29, 2, 2004).years_later(4),
+            Date::new(29, 2, 2008)
+        );
+        assert_eq!(
+            Date::new(29, 2, 2004).years_later(1),
+            Date::new(28, 2, 2005)
+        );
+    }
+
+    #[test]
+    fn next_day() {
+        assert_eq!(Date::new(31, 12, 2023).next_day(), Date::new(1, 1, 2024));
+        assert!(Date::new(6, 7, 2024).is_weekend());
+        assert!(!Date::new(8, 7, 2024).is_weekend());
+    }
 }
@@ -0,0 +1,32 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::newspaper::{Date, Year};
+
+use super::{NewspaperIssuesDTO, QueryNewspaperDTO};
+
+/// The newspapers of an anniversary: those with an issue on the day and, for the others
+/// published around it, their nearest issues.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct AnniversaryDTO {
+    years: Year,
+    date: Date,
+    newspapers: Vec<QueryNewspaperDTO>,
+    nearest: Vec<NewspaperIssuesDTO>,
+}
+
+impl AnniversaryDTO {
+    pub(crate) fn new(
+        years: Year,
+        date: Date,
+        newspapers: Vec<QueryNewspaperDTO>,
+        nearest: Vec<NewspaperIssuesDTO>,
+    ) -> Self {
+        Self {
+            years,
+            date,
+            newspapers,
+            nearest,
+        }
+    }
+}
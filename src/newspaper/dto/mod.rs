@@ -1,3 +1,11 @@
+mod anniversary;
+mod nearest_issues;
+mod newspaper_issues;
+mod newspaper_request;
 mod query_newspaper;
 
+pub(crate) use anniversary::AnniversaryDTO;
+pub(crate) use nearest_issues::NearestIssuesDTO;
+pub(crate) use newspaper_issues::NewspaperIssuesDTO;
+pub(crate) use newspaper_request::NewspaperRequest;
 pub(crate) use query_newspaper::QueryNewspaperDTO;
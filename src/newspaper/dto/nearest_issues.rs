@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::newspaper::Date;
+
+/// The issues of a newspaper closest to a date within a window of days, e.g. to offer
+/// them when it has no issue on the date. `published` tells whether it has one.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct NearestIssuesDTO {
+    signature: String,
+    name: String,
+    date: Date,
+    published: bool,
+    earlier: Option<Date>,
+    later: Option<Date>,
+}
+
+impl NearestIssuesDTO {
+    pub(crate) fn new(
+        signature: String,
+        name: String,
+        date: Date,
+        published: bool,
+        earlier: Option<Date>,
+        later: Option<Date>,
+    ) -> Self {
+        Self {
+            signature,
+            name,
+            date,
+            published,
+            earlier,
+            later,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::newspaper::Date;
+
+/// The issues of a newspaper within a period, from the earliest.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct NewspaperIssuesDTO {
+    signature: String,
+    name: String,
+    dates: Vec<Date>,
+}
+
+impl NewspaperIssuesDTO {
+    pub(crate) fn new(signature: String, name: String, dates: Vec<Date>) -> Self {
+        Self {
+            signature,
+            name,
+            dates,
+        }
+    }
+}
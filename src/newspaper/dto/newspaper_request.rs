@@ -0,0 +1,75 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::{
+    newspaper::{error::Result, Newspaper, UncheckedNewspaper},
+    Time,
+};
+
+/// A newspaper to be added to the catalog, checked once it is deserialized.
+/// Its years are checked against the given clock, so that it can be checked at any instant.
+#[derive(Deserialize, JsonSchema)]
+#[serde(from = "UncheckedNewspaper")]
+#[schemars(
+    rename = "NewspaperRequest",
+    description = "A newspaper to be added to the catalog, its years cannot be in the future"
+)]
+pub(crate) struct NewspaperRequest {
+    newspaper: UncheckedNewspaper,
+}
+
+impl NewspaperRequest {
+    pub(crate) fn check<T>(self, clock: &T) -> Result<Newspaper>
+    where
+        T: Time,
+    {
+        self.newspaper.into_checked(clock.now())
+    }
+}
+
+impl From<UncheckedNewspaper> for NewspaperRequest {
+    fn from(newspaper: UncheckedNewspaper) -> Self {
+        Self { newspaper }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        newspaper::{error::Result, Newspaper, Year},
+        services::MockClock,
+    };
+
+    use super::NewspaperRequest;
+
+    /// 31.12.2024, 23:59:59.999 UTC
+    const LAST_MS_OF_2024: u64 = 1_735_689_599_999;
+
+    #[test]
+    fn year_boundary() {
+        assert!(checked(2025, LAST_MS_OF_2024)
+            .expect_err("expected the year to be in the future")
+            .to_string()
+            .contains("start_year cannot be in the future"));
+
+        assert_eq!(
+            checked(2025, LAST_MS_OF_2024 + 1).unwrap(),
+            Newspaper::new_unchecked("В1905", "Поглед", 2025, None, [true; 7])
+        );
+    }
+
+    #[test]
+    fn past_instant() {
+        assert!(checked(1970, 0).is_ok());
+        assert!(checked(1971, 0).is_err());
+    }
+
+    fn checked(start_year: Year, now_ms: u64) -> Result<Newspaper> {
+        serde_json::from_str::<NewspaperRequest>(&format!(
+            r#"{{"signature":"В1905","name":"Поглед","start_year":{},"end_year":null,"weekly_schedule":[true,true,true,true,true,true,true]}}"#,
+            start_year
+        ))
+        .expect("the request is well-formed")
+        .check(&MockClock::at(now_ms))
+    }
+}
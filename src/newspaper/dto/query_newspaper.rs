@@ -1,9 +1,10 @@
+use schemars::JsonSchema;
 use serde::Serialize;
 
 #[cfg(test)]
 use serde::Deserialize;
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq, Hash, Deserialize))]
 pub struct QueryNewspaperDTO {
     signature: String,
@@ -3,7 +3,7 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub(crate) enum Error {
     #[error("[Newspaper] Error parsing date {0}")]
-    DateParsing(chrono::ParseError),
+    DateParsing(String),
 
     #[error("[Newspaper] Signature does not match the required pattern.")]
     SignatureMismatch,
@@ -11,14 +11,26 @@ pub(crate) enum Error {
     #[error("Invalid year: {0}")]
     InvalidYear(&'static str),
 
+    #[error("[Newspaper] Invalid period: {0}")]
+    InvalidPeriod(&'static str),
+
     #[error("End year is already set for this newspaper.")]
     EndYearExists,
 
-    #[error("[Newspaper] Problem while deserialization: {0}")]
-    DeserializationFault(serde_json::Error),
-
     #[error("[Newspaper] Problem while serialization: {0}")]
     SerializationFault(serde_json::Error),
+
+    #[error("[Newspaper] Expected 11 columns, but found {0}")]
+    ColumnCount(usize),
+
+    #[error("[Newspaper] Cannot parse the year {0}")]
+    YearParsing(String),
+
+    #[error("[Newspaper] Invalid schedule day {0}: expected 0, 1 or the name of the day")]
+    ScheduleDay(String),
+
+    #[error("[Newspaper] The CSV has a quoted field without a closing quote")]
+    UnterminatedQuote,
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -1,13 +1,13 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Represents the frequency of publication of a newspaper over the course of a week.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[serde(transparent)]
 pub(crate) struct WeeklyFrequency([bool; 7]);
 
 impl WeeklyFrequency {
-    #[cfg(test)]
     pub(crate) fn new(days: [bool; 7]) -> Self {
         Self(days)
     }
@@ -1,17 +1,23 @@
+mod calendar;
+pub(crate) mod csv;
 mod date;
 mod dto;
 mod error;
 mod frequency;
+#[allow(clippy::module_inception)]
 mod newspaper;
 mod signature;
+mod written;
 
-pub(crate) use date::Date;
+pub(crate) use calendar::{anniversary_issues, nearest_issues, newspapers_in_range};
+pub(crate) use date::{Date, DateFormat};
 pub(crate) use error::Error;
-pub(super) use newspaper::{newspapers_by_date, Newspaper};
+pub(super) use newspaper::{extract_year, newspapers_by_date, Newspaper, UncheckedNewspaper};
 
-#[cfg(test)]
-pub(crate) use dto::QueryNewspaperDTO;
+pub(crate) use dto::{
+    AnniversaryDTO, NearestIssuesDTO, NewspaperIssuesDTO, NewspaperRequest, QueryNewspaperDTO,
+};
 pub(super) use frequency::WeeklyFrequency;
-pub(super) use signature::Signature;
+pub(super) use signature::{next_letter, Signature, AFTER_SIGN, SIGN};
 
 pub(crate) type Year = u16;
@@ -1,25 +1,26 @@
 use chrono::{DateTime, Datelike};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use std::result::Result as StdResult;
-
 #[cfg(test)]
-use crate::services::MockHost;
+use crate::services::MockClock;
 use crate::{
     bindings::{component::dnevest::time::Clock, ByteArray},
-    Host, Storage, Time,
+    repo::NewspaperRepo,
+    Storage, Time,
 };
 
 use super::{
     dto::QueryNewspaperDTO,
     error::{Error, Result},
-    signature::{self, Signature},
+    signature::Signature,
     Date, WeeklyFrequency, Year,
 };
 
+/// A newspaper holding its invariants at the time it was checked. It is deserialized
+/// only through `NewspaperRequest`, which is checked against a clock.
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
-#[serde(try_from = "UncheckedNewspaper")]
+#[derive(Serialize, JsonSchema)]
 pub(crate) struct Newspaper {
     signature: Signature,
     name: String,
@@ -50,6 +51,14 @@ impl Newspaper {
         self.signature.as_str()
     }
 
+    pub(crate) fn end_year(&self) -> Option<Year> {
+        self.end_year
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
     pub(crate) fn add_end_year(self, end_year: Year, now: Clock) -> Result<Self> {
         let current_year = extract_year(now.timestamp);
         self.end_year.map_or(
@@ -84,27 +93,30 @@ impl Newspaper {
     }
 
     fn invariant_held(&self, current_year: Year) -> Result<()> {
-        (self.start_year > current_year)
-            .then_some(Err(Error::InvalidYear(
-                "start_year cannot be in the future",
-            )))
-            .unwrap_or({
-                self.end_year
-                    .filter(|end| self.start_year > *end)
-                    .map(|_| Err(Error::InvalidYear("start_year cannot be after end_year")))
-                    .unwrap_or(Ok(()))
-            })
+        if self.start_year > current_year {
+            Err(Error::InvalidYear("start_year cannot be in the future"))
+        } else {
+            self.end_year
+                .filter(|end| self.start_year > *end)
+                .map(|_| Err(Error::InvalidYear("start_year cannot be after end_year")))
+                .unwrap_or(Ok(()))
+        }
     }
 
     fn published_on(&self, day_index: usize, year: Year) -> bool {
         self.start_year <= year
-            && self.end_year.map_or(true, |end| end >= year)
+            && self.end_year.is_none_or(|end| end >= year)
             && self.weekly_schedule.published_on(day_index)
     }
+
+    /// Whether the newspaper has an issue on the date.
+    pub(super) fn has_issue_on(&self, date: &Date) -> bool {
+        self.published_on(date.day_index(), date.year())
+    }
 }
 
-#[derive(Deserialize)]
-struct UncheckedNewspaper {
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(crate) struct UncheckedNewspaper {
     signature: Signature,
     name: String,
     start_year: Year,
@@ -113,7 +125,43 @@ struct UncheckedNewspaper {
 }
 
 impl UncheckedNewspaper {
-    fn into_checked(self, now: Clock) -> Result<Newspaper> {
+    pub(super) fn new(
+        signature: Signature,
+        name: String,
+        start_year: Year,
+        end_year: Option<Year>,
+        weekly_schedule: WeeklyFrequency,
+    ) -> Self {
+        Self {
+            signature,
+            name,
+            start_year,
+            end_year,
+            weekly_schedule,
+        }
+    }
+
+    pub(crate) fn signature(&self) -> &str {
+        self.signature.as_str()
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(super) fn start_year(&self) -> Year {
+        self.start_year
+    }
+
+    pub(super) fn end_year(&self) -> Option<Year> {
+        self.end_year
+    }
+
+    pub(super) fn published_on(&self, day_index: usize) -> bool {
+        self.weekly_schedule.published_on(day_index)
+    }
+
+    pub(crate) fn into_checked(self, now: Clock) -> Result<Newspaper> {
         let current_year = extract_year(now.timestamp);
         let obj = Newspaper::new(
             self.signature,
@@ -126,15 +174,20 @@ impl UncheckedNewspaper {
     }
 }
 
-impl TryFrom<UncheckedNewspaper> for Newspaper {
-    type Error = Error;
-
-    fn try_from(unchecked: UncheckedNewspaper) -> StdResult<Self, Self::Error> {
-        unchecked.into_checked(Host::now())
+/// A checked newspaper is stored as it is, the invariants are checked again once loaded.
+impl From<&Newspaper> for UncheckedNewspaper {
+    fn from(newspaper: &Newspaper) -> Self {
+        Self::new(
+            newspaper.signature.clone(),
+            newspaper.name.clone(),
+            newspaper.start_year,
+            newspaper.end_year,
+            newspaper.weekly_schedule.clone(),
+        )
     }
 }
 
-fn extract_year(millis: u64) -> Year {
+pub(crate) fn extract_year(millis: u64) -> Year {
     let datetime = DateTime::from_timestamp_millis(
         i64::try_from(millis).expect("u64 value is too large for i64"),
     )
@@ -144,7 +197,7 @@ fn extract_year(millis: u64) -> Year {
 
 #[cfg(test)]
 fn try_from_unchecked(unchecked: UncheckedNewspaper) -> Result<Newspaper> {
-    unchecked.into_checked(MockHost::now())
+    unchecked.into_checked(MockClock::default().now())
 }
 
 impl From<Newspaper> for QueryNewspaperDTO {
@@ -153,39 +206,42 @@ impl From<Newspaper> for QueryNewspaperDTO {
     }
 }
 
-pub(crate) fn newspapers_by_date<S, T>(date: Date) -> Result<ByteArray>
+pub(crate) fn newspapers_by_date<S, T>(date: Date, clock: &T) -> Result<ByteArray>
 where
     S: Storage + Default,
-    T: Time + Default,
+    T: Time,
 {
-    let year = date.year();
-    let day = (date.day_of_week().number_from_monday() - 1) as usize;
-
-    let published_newspapers: Vec<QueryNewspaperDTO> = S::default()
-        .retrieve_range(
-            &signature::SIGN.to_string(),
-            &signature::next_letter(signature::SIGN).to_string(),
-        )
+    let published_newspapers: Vec<QueryNewspaperDTO> = self::catalog::<S, T>(clock)
         .into_iter()
-        .filter_map(|ser_newspaper| {
-            serde_json::from_slice::<UncheckedNewspaper>(&ser_newspaper)
-                .map_err(Error::DeserializationFault)
-                .and_then(|unchecked| unchecked.into_checked(T::now()))
-                .ok()
-                .and_then(|newspaper| newspaper.published_on(day, year).then(|| newspaper.into()))
-        })
+        .filter_map(|newspaper| newspaper.has_issue_on(&date).then(|| newspaper.into()))
         .collect::<Vec<QueryNewspaperDTO>>();
 
     serde_json::to_vec(&published_newspapers).map_err(Error::SerializationFault)
 }
 
+/// The newspapers of the catalog holding their invariants, the others are left out.
+pub(super) fn catalog<S, T>(clock: &T) -> Vec<Newspaper>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    NewspaperRepo::all(&S::default())
+        .into_iter()
+        .filter_map(|unchecked| {
+            unchecked
+                .ok()
+                .and_then(|unchecked| unchecked.into_checked(clock.now()).ok())
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
     use crate::{
         newspaper::{Date, QueryNewspaperDTO, Year},
-        services::MockHost,
+        services::{MockClock, MockHost},
         Time,
     };
 
@@ -257,7 +313,7 @@ mod tests {
 
     #[test]
     fn existing_end_year() {
-        let res = Newspaper::add_end_year(newspaper(), 1998, MockHost::now());
+        let res = Newspaper::add_end_year(newspaper(), 1998, MockClock::default().now());
         let err = Error::EndYearExists;
 
         assert_err(res, err.to_string());
@@ -272,13 +328,16 @@ mod tests {
             None,
             [true, false, false, true, false, true, false],
         );
-        let res = Newspaper::add_end_year(obj, 1989, MockHost::now());
+        let res = Newspaper::add_end_year(obj, 1989, MockClock::default().now());
         assert_eq!(res.unwrap(), newspaper())
     }
 
     fn publicized_on(day: u16, month: u16, year: Year) -> Vec<QueryNewspaperDTO> {
-        let res = super::newspapers_by_date::<MockHost, MockHost>(Date::new(day, month, year))
-            .expect("Failed to retrieve newspapers published on the specified date");
+        let res = super::newspapers_by_date::<MockHost, MockClock>(
+            Date::new(day, month, year),
+            &MockClock::default(),
+        )
+        .expect("Failed to retrieve newspapers published on the specified date");
         serde_json::from_slice(&res)
             .expect("Failed to deserialize the published newspapers from the result")
     }
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use std::result::Result as StdResult;
@@ -5,11 +6,17 @@ use std::result::Result as StdResult;
 use super::error::{Error, Result};
 
 pub(crate) const SIGN: char = 'В';
+/// The invariant in the regular expressions of JSON Schema.
+const PATTERN: &str = "^В(?!0000)[0-9]{4}$";
 
 /// Brings invariant checking as a step in deserializing a Newspaper
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(try_from = "String")]
+#[schemars(
+    description = "The signature of a newspaper, e.g. В1645",
+    extend("pattern" = PATTERN)
+)]
 pub(crate) struct Signature(String);
 
 impl Signature {
@@ -48,11 +55,16 @@ impl TryFrom<String> for Signature {
     }
 }
 
-pub(crate) fn next_letter(c: char) -> char {
-    u32::try_from(c)
-        .ok()
-        .and_then(|val| char::try_from(val + 1).ok())
-        .unwrap()
+/// The letter after the sign, ending the range of the signatures.
+pub(crate) const AFTER_SIGN: char = next_letter(SIGN);
+
+/// Evaluated at compile time for the constants, so a letter without a successor
+/// fails the build instead of a request.
+pub(crate) const fn next_letter(c: char) -> char {
+    match char::from_u32(c as u32 + 1) {
+        Some(next) => next,
+        None => panic!("the letter has no successor"),
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,393 @@
+use serde::{
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize, Serializer,
+};
+
+use super::date::{DateFormat, DATE};
+
+/// A response or an event with its dates written in the format. Every other value is
+/// serialized as it is, so the dates are told apart by the name they are serialized under.
+pub(super) struct Written<'a, V: ?Sized> {
+    value: &'a V,
+    format: DateFormat,
+}
+
+impl<'a, V: ?Sized> Written<'a, V> {
+    pub(super) fn new(value: &'a V, format: DateFormat) -> Self {
+        Self { value, format }
+    }
+}
+
+impl<V> Serialize for Written<'_, V>
+where
+    V: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(Writer {
+            inner: serializer,
+            format: self.format,
+        })
+    }
+}
+
+/// Passes everything on to the inner serializer, apart from the dates.
+struct Writer<S> {
+    inner: S,
+    format: DateFormat,
+}
+
+/// A sequence, a map or a struct, whose items may hold dates as well.
+struct Compound<C> {
+    inner: C,
+    format: DateFormat,
+}
+
+impl<S> Writer<S> {
+    fn written<'a, V: ?Sized>(&self, value: &'a V) -> Written<'a, V> {
+        Written::new(value, self.format)
+    }
+}
+
+impl<C> Compound<C> {
+    fn written<'a, V: ?Sized>(&self, value: &'a V) -> Written<'a, V> {
+        Written::new(value, self.format)
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($type:ty)),* $(,)?) => {
+        $(
+            fn $method(self, value: $type) -> Result<S::Ok, S::Error> {
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<S> Serializer for Writer<S>
+where
+    S: Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    );
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_some(&value)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_variant(name, index, variant)
+    }
+
+    /// A date is written in the default format, so it is read back and written again.
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        if name == DATE {
+            let default = serde_json::to_value(value).map_err(ser::Error::custom)?;
+            let rewritten = default
+                .as_str()
+                .and_then(|date| self.format.rewrite(date))
+                .ok_or_else(|| ser::Error::custom(format!("not a date: {}", default)))?;
+            self.inner.serialize_str(&rewritten)
+        } else {
+            let value = self.written(value);
+            self.inner.serialize_newtype_struct(name, &value)
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner
+            .serialize_newtype_variant(name, index, variant, &value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_seq(len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_tuple(len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_tuple_struct(name, len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_tuple_variant(name, index, variant, len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_map(len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_struct(name, len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        let format = self.format;
+        self.inner
+            .serialize_struct_variant(name, index, variant, len)
+            .map(|inner| Compound { inner, format })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+impl<C> SerializeSeq for Compound<C>
+where
+    C: SerializeSeq,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeTuple for Compound<C>
+where
+    C: SerializeTuple,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeTupleStruct for Compound<C>
+where
+    C: SerializeTupleStruct,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeTupleVariant for Compound<C>
+where
+    C: SerializeTupleVariant,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeMap for Compound<C>
+where
+    C: SerializeMap,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.written(key);
+        self.inner.serialize_key(&key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeStruct for Compound<C>
+where
+    C: SerializeStruct,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeStructVariant for Compound<C>
+where
+    C: SerializeStructVariant,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), C::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.written(value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
@@ -1,13 +1,14 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use std::{marker::PhantomData, result::Result as StdResult};
 
-use crate::{newspaper::Signature, Storage};
+use crate::{newspaper::Signature, repo::NewspaperRepo, Storage};
 
 use super::{Error, Result};
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub(super) struct Cover {
     preference: Signature,
     options: [Option<Signature>; 2],
@@ -21,22 +22,28 @@ impl Cover {
         }
     }
 
-    fn invariant_held<S>(&self, storage: S) -> Result<()>
+    pub(super) fn signatures(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.preference)
+            .chain(self.options.iter().flatten())
+            .map(Signature::as_str)
+    }
+
+    fn invariant_held<S>(&self, storage: &S) -> Result<()>
     where
         S: Storage,
     {
-        storage
-            .retrieve(self.preference.as_str())
+        NewspaperRepo::contains(storage, self.preference.as_str())
+            .then_some(())
             .ok_or_else(|| {
                 Error::NotFound(format!(
                     "The signature {} is not found",
                     self.preference.as_str()
                 ))
             })
-            .and_then(|_| {
+            .and_then(|()| {
                 if self.options.iter().any(|opt| {
-                    opt.as_ref().map_or(false, |signature| {
-                        storage.retrieve(signature.as_str()).is_none()
+                    opt.as_ref().is_some_and(|signature| {
+                        !NewspaperRepo::contains(storage, signature.as_str())
                     })
                 }) {
                     Err(Error::NotFound("The signature is not found".into()))
@@ -48,7 +55,8 @@ impl Cover {
 }
 
 #[cfg_attr(test, derive(Debug))]
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
+#[schemars(bound = "", rename = "Cover")]
 pub(super) struct UncheckedCover<S>
 where
     S: Storage + Default,
@@ -59,6 +67,20 @@ where
     _storage: PhantomData<S>,
 }
 
+impl<S> UncheckedCover<S>
+where
+    S: Storage + Default,
+{
+    /// Checks the cover against the catalog of the given storage instead of the one of `S`.
+    pub(super) fn check<St>(self, storage: &St) -> Result<Cover>
+    where
+        St: Storage,
+    {
+        let obj = Cover::new_unchecked(self.preference, self.options);
+        obj.invariant_held(storage).map(|()| obj)
+    }
+}
+
 impl<S> TryFrom<UncheckedCover<S>> for Cover
 where
     S: Storage + Default,
@@ -66,8 +88,7 @@ where
     type Error = Error;
 
     fn try_from(unchecked: UncheckedCover<S>) -> StdResult<Self, Self::Error> {
-        let obj = Self::new_unchecked(unchecked.preference, unchecked.options);
-        obj.invariant_held(S::default()).map(|()| obj)
+        unchecked.check(&S::default())
     }
 }
 
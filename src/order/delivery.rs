@@ -1,16 +1,21 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use std::result::Result as StdResult;
 
-use super::{Error, Result};
+use crate::newspaper::Date;
+
+use super::{phone, schedule::Estimate, Error, Result};
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub(super) struct Delivery {
     customer_names: String,
     phone_number: String,
     address: String,
     priority: Priority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    requested_date: Option<Date>,
 }
 
 impl Delivery {
@@ -19,12 +24,14 @@ impl Delivery {
         phone_number: String,
         address: String,
         priority: Priority,
+        requested_date: Option<Date>,
     ) -> Self {
         Self {
             customer_names,
             phone_number,
             address,
             priority,
+            requested_date,
         }
     }
 
@@ -32,9 +39,32 @@ impl Delivery {
         &self.phone_number
     }
 
-    // TODO: check address
-    pub(super) fn invariant_held(&self) -> Result<()> {
-        check_names(&self.customer_names).and_then(|()| check_phone(&self.phone_number))
+    /// Clears everything that identifies the customer.
+    pub(super) fn anonymize(self) -> Self {
+        Self {
+            customer_names: String::new(),
+            phone_number: String::new(),
+            address: String::new(),
+            ..self
+        }
+    }
+
+    pub(super) fn priority(&self) -> &Priority {
+        &self.priority
+    }
+
+    /// Express delivery is paid for to arrive on time, so it is rejected if it cannot.
+    pub(super) fn check_arrival(&self, estimate: &Estimate) -> Result<()> {
+        match (&self.priority, self.requested_date) {
+            (Priority::Express, Some(requested)) if requested < *estimate.deliver_on() => {
+                Err(Error::UnreachableDate(format!(
+                    "Express delivery cannot arrive by {}, the earliest delivery date is {}",
+                    String::from(requested),
+                    String::from(*estimate.deliver_on())
+                )))
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -48,57 +78,53 @@ fn check_names(names: &str) -> Result<()> {
     }
 }
 
-fn check_phone(number: &str) -> Result<()> {
-    number
-        .strip_prefix("+359")
-        .or_else(|| number.strip_prefix("0"))
-        .map_or_else(
-            || {
-                Err(Error::InvalidDelivery(
-                    "Phone number must start with 0 or +359",
-                ))
-            },
-            |digits_only| {
-                if digits_only.len() == 9 && digits_only.chars().all(|c| c.is_ascii_digit()) {
-                    Ok(())
-                } else {
-                    Err(Error::InvalidDelivery("Wrong number of digits"))
-                }
-            },
-        )
+/// Returns the number in canonical E.164 form so that the same customer is always
+/// identified by the same phone, no matter how it was typed.
+fn check_phone(number: &str) -> Result<String> {
+    phone::normalize(number, phone::SUPPORTED_COUNTRIES)
 }
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub(super) enum Priority {
     Standart,
     Express,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "Delivery")]
 pub(super) struct UncheckedDelivery {
     customer_names: String,
+    #[schemars(extend("pattern" = phone::PATTERN))]
     phone_number: String,
     address: String,
     priority: Priority,
+    requested_date: Option<Date>,
 }
 
 impl TryFrom<UncheckedDelivery> for Delivery {
     type Error = Error;
 
+    // TODO: check address
     fn try_from(unchecked: UncheckedDelivery) -> StdResult<Self, Self::Error> {
-        let obj = Self::new_unchecked(
-            unchecked.customer_names,
-            unchecked.phone_number,
-            unchecked.address,
-            unchecked.priority,
-        );
-        obj.invariant_held().map(|()| obj)
+        check_names(&unchecked.customer_names)
+            .and_then(|()| check_phone(&unchecked.phone_number))
+            .map(|phone_number| {
+                Self::new_unchecked(
+                    unchecked.customer_names,
+                    phone_number,
+                    unchecked.address,
+                    unchecked.priority,
+                    unchecked.requested_date,
+                )
+            })
     }
 }
 
 #[cfg(test)]
 mod test_invariant {
+    use std::fmt::Debug;
+
     use super::{check_names, check_phone, Result};
 
     #[test]
@@ -123,6 +149,13 @@ mod test_invariant {
     fn valid_phone() {
         assert!(check_phone("0893471823").is_ok());
         assert!(check_phone("+359461839203").is_ok());
+        assert!(check_phone("+4915123456789").is_ok());
+    }
+
+    #[test]
+    fn canonical_phone() {
+        assert_eq!(check_phone("0893471823").unwrap(), "+359893471823");
+        assert_eq!(check_phone("00359893471823").unwrap(), "+359893471823");
     }
 
     #[test]
@@ -133,20 +166,25 @@ mod test_invariant {
         );
 
         let msg = "Wrong number of digits";
-        assert_err(check_phone("038776492"), msg);
-        assert_err(check_phone("+35938776492"), msg);
+        assert_err(check_phone("03877"), msg);
+        assert_err(check_phone("+3593877"), msg);
         assert_err(check_phone("+359 238776492"), msg);
     }
 
-    fn assert_err(r: Result<()>, msg: &str) {
+    fn assert_err<T: Debug>(r: Result<T>, msg: &str) {
         assert!(r.expect_err("expected an error").to_string().contains(msg))
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::{newspaper::Date, order::schedule::DeliverySchedule};
+
     use super::{Delivery, Priority, Result, UncheckedDelivery};
 
+    // 29-11-2024 (Friday) 13:39:55 in Bulgaria, before the default cut-off
+    const NOW: u64 = 1732880395000;
+
     #[test]
     fn unchecked_deserialization() {
         let json = r#"{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}"#;
@@ -163,6 +201,15 @@ mod test {
         assert_err(res, "Phone number must start with 0 or +359")
     }
 
+    #[test]
+    fn normalized_phone() {
+        let json = r#"{"customer_names":"Тодор Георгиев","phone_number":"00359873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}"#;
+        let unchecked: UncheckedDelivery =
+            serde_json::from_str(json).expect("failed to deserialize JSON");
+        let res: Delivery = unchecked.try_into().expect("invalid delivery");
+        assert_eq!(res.phone(), "+359873528495")
+    }
+
     #[test]
     fn serialize() {
         let waybill = delivery();
@@ -173,12 +220,52 @@ mod test {
         )
     }
 
+    #[test]
+    fn express_arrival() {
+        let schedule = DeliverySchedule::default();
+        let on_time = express(Date::new(2, 12, 2024));
+        assert!(on_time
+            .check_arrival(&schedule.estimate(NOW, on_time.priority()))
+            .is_ok());
+
+        let too_early = express(Date::new(30, 11, 2024));
+        let res = too_early.check_arrival(&schedule.estimate(NOW, too_early.priority()));
+        assert!(res
+            .expect_err("expected an error")
+            .to_string()
+            .contains(&format!(
+                "Express delivery cannot arrive by {}, the earliest delivery date is {}",
+                String::from(Date::new(30, 11, 2024)),
+                String::from(Date::new(2, 12, 2024))
+            )));
+    }
+
+    #[test]
+    fn standard_arrival_not_rejected() {
+        let mut standard = delivery();
+        standard.requested_date = Some(Date::new(30, 11, 2024));
+        assert!(standard
+            .check_arrival(&DeliverySchedule::default().estimate(NOW, standard.priority()))
+            .is_ok());
+    }
+
+    fn express(requested_date: Date) -> Delivery {
+        Delivery::new_unchecked(
+            "Тодор Георгиев".to_string(),
+            "+359873528495".to_string(),
+            "Пловдив, ул.Тракия 12".to_string(),
+            Priority::Express,
+            Some(requested_date),
+        )
+    }
+
     fn delivery() -> Delivery {
         Delivery::new_unchecked(
             "Тодор Георгиев".to_string(),
             "0873528495".to_string(),
             "Пловдив, ул.Тракия 12".to_string(),
             Priority::Standart,
+            None,
         )
     }
 
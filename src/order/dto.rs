@@ -1,38 +1,79 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-use std::{marker::PhantomData, result::Result as StdResult};
+use std::marker::PhantomData;
 
 use crate::{Storage, Time};
 
-use super::{delivery::UncheckedDelivery, wish_card::UncheckedWishCard, Error, Order};
+use super::{
+    delivery::{Delivery, UncheckedDelivery},
+    line_item::{self, LineItem, UncheckedLineItem},
+    price::PriceList,
+    schedule::DeliverySchedule,
+    wish_card::UncheckedWishCard,
+    Error, Order, Result,
+};
 
-#[derive(Deserialize)]
+/// Accepts either a single `wish_card`, ordered once, or a list of `items`,
+/// each with its own wish card and quantity.
+#[derive(Deserialize, JsonSchema)]
 #[serde(bound = "")]
-pub(crate) struct OrderRequest<S, T>
+#[schemars(bound = "", rename = "OrderRequest")]
+pub(crate) struct OrderRequest<S>
 where
     S: Storage + Default,
-    T: Time + Default,
 {
-    wish_card: UncheckedWishCard<S>,
+    #[serde(default)]
+    wish_card: Option<UncheckedWishCard<S>>,
+    #[serde(default)]
+    items: Vec<UncheckedLineItem<S>>,
     delivery: UncheckedDelivery,
     #[serde(skip)]
     _storage: PhantomData<S>,
-    #[serde(skip)]
-    _time: PhantomData<T>,
 }
 
-impl<S, T> TryFrom<OrderRequest<S, T>> for Order
+impl<S> OrderRequest<S>
 where
     S: Storage + Default,
-    T: Time + Default,
 {
-    type Error = Error;
-
-    fn try_from(dto: OrderRequest<S, T>) -> StdResult<Self, Self::Error> {
-        dto.wish_card.try_into().and_then(|wish_card| {
-            dto.delivery
-                .try_into()
-                .map(|delivery| Order::new_unchecked(wish_card, delivery, T::now().timestamp))
+    /// Checks the order against the catalog and the settings of the given storage instead
+    /// of the ones of `S`, e.g. of a batch seeing the changes of its previous messages,
+    /// and places it at the time of the clock.
+    pub(crate) fn check<St, T>(self, storage: &St, clock: &T) -> Result<Order>
+    where
+        St: Storage,
+        T: Time,
+    {
+        let now = clock.now().timestamp;
+        Self::line_items(self.wish_card, self.items, storage).and_then(|items| {
+            self.delivery.try_into().and_then(|delivery: Delivery| {
+                DeliverySchedule::load(storage).and_then(|schedule| {
+                    let estimate = schedule.estimate(now, delivery.priority());
+                    delivery.check_arrival(&estimate).and_then(|()| {
+                        PriceList::load(storage).map(|prices| {
+                            let price = prices.price(&items, delivery.priority());
+                            Order::new_unchecked(items, delivery, estimate, now).with_price(price)
+                        })
+                    })
+                })
+            })
         })
     }
+
+    fn line_items<St>(
+        wish_card: Option<UncheckedWishCard<S>>,
+        items: Vec<UncheckedLineItem<S>>,
+        storage: &St,
+    ) -> Result<Vec<LineItem>>
+    where
+        St: Storage,
+    {
+        match wish_card {
+            Some(_) if !items.is_empty() => Err(Error::AmbiguousItems),
+            Some(wish_card) => wish_card
+                .check(storage)
+                .map(|wish_card| vec![LineItem::new_unchecked(wish_card, 1)]),
+            None => line_item::check_all(items, storage),
+        }
+    }
 }
@@ -1,18 +1,49 @@
 use thiserror::Error;
 
+use crate::repo;
+
 #[derive(Error, Debug)]
 pub(crate) enum Error {
     #[error("[order] The card number does not exist.")]
     InvalidCard,
 
-    #[error("[order] Problem while deserialization: {0}")]
-    DeserializationFault(serde_json::Error),
+    #[error("[order] The quantity must be at least 1.")]
+    InvalidQuantity,
+
+    #[error("[order] The order has to contain at least one wish card.")]
+    NoItems,
+
+    #[error("[order] The order has to contain either a wish card or items, not both.")]
+    AmbiguousItems,
+
+    #[error("[order] Invalid items: {}", describe_lines(.0))]
+    InvalidItems(Vec<(usize, Error)>),
+
+    #[error("[order] {0}")]
+    Storage(#[from] repo::Error),
 
     #[error("[order] {0}.")]
     NotFound(String),
 
     #[error("[order] Invalid waybill invariant: {0}")]
     InvalidDelivery(&'static str),
+
+    #[error("[order] Invalid delivery schedule: {0}")]
+    InvalidSchedule(&'static str),
+
+    #[error("[order] {0}.")]
+    UnreachableDate(String),
+
+    #[error("[order] Invalid price list: {0}")]
+    InvalidPriceList(&'static str),
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
+
+fn describe_lines(errors: &[(usize, Error)]) -> String {
+    errors
+        .iter()
+        .map(|(line, err)| format!("items[{}]: {}", line, err))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
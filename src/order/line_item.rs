@@ -0,0 +1,151 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::Storage;
+
+use super::{
+    wish_card::{UncheckedWishCard, WishCard},
+    Error, Result,
+};
+
+/// A wish card together with the number of copies ordered.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(super) struct LineItem {
+    wish_card: WishCard,
+    quantity: u8,
+}
+
+impl LineItem {
+    pub(super) fn new_unchecked(wish_card: WishCard, quantity: u8) -> Self {
+        Self {
+            wish_card,
+            quantity,
+        }
+    }
+
+    pub(super) fn quantity(&self) -> u8 {
+        self.quantity
+    }
+
+    pub(super) fn signatures(&self) -> impl Iterator<Item = &str> {
+        self.wish_card.signatures()
+    }
+
+    pub(super) fn anonymize(self) -> Self {
+        Self {
+            wish_card: self.wish_card.anonymize(),
+            ..self
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(bound = "")]
+#[schemars(bound = "", rename = "LineItem")]
+pub(super) struct UncheckedLineItem<S>
+where
+    S: Storage + Default,
+{
+    wish_card: UncheckedWishCard<S>,
+    quantity: u8,
+}
+
+impl<S> UncheckedLineItem<S>
+where
+    S: Storage + Default,
+{
+    fn check<St>(self, storage: &St) -> Result<LineItem>
+    where
+        St: Storage,
+    {
+        if self.quantity == 0 {
+            Err(Error::InvalidQuantity)
+        } else {
+            self.wish_card
+                .check(storage)
+                .map(|wish_card| LineItem::new_unchecked(wish_card, self.quantity))
+        }
+    }
+}
+
+/// Validates every line on its own, so that the customer gets all the problems at once,
+/// each one pointing to the line it comes from.
+pub(super) fn check_all<S, St>(
+    items: Vec<UncheckedLineItem<S>>,
+    storage: &St,
+) -> Result<Vec<LineItem>>
+where
+    S: Storage + Default,
+    St: Storage,
+{
+    if items.is_empty() {
+        return Err(Error::NoItems);
+    }
+
+    let (checked, errors): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .map(|item| item.check(storage))
+        .enumerate()
+        .partition(|(_, res)| res.is_ok());
+
+    if errors.is_empty() {
+        Ok(checked
+            .into_iter()
+            .filter_map(|(_, item)| item.ok())
+            .collect())
+    } else {
+        Err(Error::InvalidItems(
+            errors
+                .into_iter()
+                .filter_map(|(line, item)| item.err().map(|err| (line, err)))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::services::MockHost;
+
+    use super::{Result, UncheckedLineItem};
+
+    const CARD: &str = r#"{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10}"#;
+    const INVALID_CARD: &str = r#"{"covers":{"preference":"В2364","options":[null,null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10}"#;
+
+    #[test]
+    fn valid_items() {
+        let res = super::check_all(vec![item(CARD, 2), item(CARD, 1)], &MockHost::default());
+        assert_eq!(res.expect("valid items").len(), 2);
+    }
+
+    #[test]
+    fn errors_indexed_by_line() {
+        let res = super::check_all(
+            vec![item(CARD, 1), item(INVALID_CARD, 1), item(CARD, 0)],
+            &MockHost::default(),
+        );
+        assert_err(
+            res,
+            "items[1]: [order] The signature В2364 is not found.; items[2]: [order] The quantity must be at least 1.",
+        );
+    }
+
+    #[test]
+    fn no_items() {
+        assert_err(
+            super::check_all::<MockHost, _>(Vec::new(), &MockHost::default()),
+            "The order has to contain at least one wish card",
+        );
+    }
+
+    fn item(wish_card: &str, quantity: u8) -> UncheckedLineItem<MockHost> {
+        let json = format!(r#"{{"wish_card":{},"quantity":{}}}"#, wish_card, quantity);
+        serde_json::from_str(&json).expect("failed to deserialize JSON")
+    }
+
+    fn assert_err<T>(r: Result<T>, msg: &str) {
+        let err = r.err().expect("expected an error").to_string();
+        assert!(err.contains(msg), "{}", err)
+    }
+}
@@ -1,36 +1,137 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::newspaper::{self, Date, Year};
+
 mod cover;
 mod delivery;
 mod dto;
 mod error;
+mod line_item;
+mod number;
+mod phone;
+mod price;
+mod retention;
+mod schedule;
+mod summary;
+mod upgrade;
 mod wish_card;
 
 use delivery::Delivery;
 pub(crate) use dto::OrderRequest;
 pub(crate) use error::Error;
 use error::Result;
+use line_item::LineItem;
+pub(crate) use number::{NumberKey, OrderNumber};
+pub(crate) use phone::PhoneNumber;
+pub(crate) use price::{Price, PriceList};
+pub(crate) use retention::RetentionPeriod;
+pub(crate) use schedule::DeliverySchedule;
+use schedule::Estimate;
+pub(crate) use summary::OrderSummary;
+pub(crate) use upgrade::to_line_items;
 pub(crate) use wish_card::MaxCards;
-use wish_card::WishCard;
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub(crate) struct Order {
-    wish_card: WishCard,
+    items: Vec<LineItem>,
     delivery: Delivery,
+    estimate: Estimate,
     created_on_ms: u64,
+    number: Option<OrderNumber>,
+    #[serde(default)]
+    anonymized: bool,
+    /// The orders placed before the cards were priced have no price.
+    #[serde(default)]
+    price: Option<Price>,
 }
 
 impl Order {
-    fn new_unchecked(wish_card: WishCard, delivery: Delivery, created_on_ms: u64) -> Self {
+    fn new_unchecked(
+        items: Vec<LineItem>,
+        delivery: Delivery,
+        estimate: Estimate,
+        created_on_ms: u64,
+    ) -> Self {
         Self {
-            wish_card,
+            items,
             delivery,
+            estimate,
             created_on_ms,
+            number: None,
+            anonymized: false,
+            price: None,
         }
     }
 
+    /// The key of the orders placed before they were numbered, which contains the phone.
     pub(crate) fn identifier(&self) -> String {
         format!("{}_{}", self.created_on_ms, self.delivery.phone())
     }
+
+    pub(crate) fn created_on_ms(&self) -> u64 {
+        self.created_on_ms
+    }
+
+    pub(crate) fn year(&self) -> Year {
+        newspaper::extract_year(self.created_on_ms)
+    }
+
+    pub(crate) fn with_number(self, number: OrderNumber) -> Self {
+        Self {
+            number: Some(number),
+            ..self
+        }
+    }
+
+    fn with_price(self, price: Price) -> Self {
+        Self {
+            price: Some(price),
+            ..self
+        }
+    }
+
+    pub(crate) fn summary(&self) -> OrderSummary {
+        OrderSummary::from(self)
+    }
+
+    pub(crate) fn number(&self) -> Option<&OrderNumber> {
+        self.number.as_ref()
+    }
+
+    pub(crate) fn phone(&self) -> &str {
+        self.delivery.phone()
+    }
+
+    /// Whether the order was placed from the phone. The orders placed before the phones were
+    /// normalized hold the phone as it was typed, e.g. "0873528495", so it is normalized first.
+    pub(crate) fn placed_by(&self, phone: &PhoneNumber) -> bool {
+        PhoneNumber::try_from(self.phone().to_string())
+            .is_ok_and(|own| own.as_str() == phone.as_str())
+    }
+
+    pub(crate) fn is_anonymized(&self) -> bool {
+        self.anonymized
+    }
+
+    /// The newspapers whose covers are printed on the cards.
+    pub(crate) fn signatures(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().flat_map(LineItem::signatures)
+    }
+
+    pub(crate) fn delivered_before(&self, date: &Date) -> bool {
+        self.estimate.deliver_on() < date
+    }
+
+    /// Removes the personal data of the customer, keeping what is needed for the accounting
+    /// and the statistics - the number, the dates, the priority, the ordered cards and the price.
+    pub(crate) fn anonymize(self) -> Self {
+        Self {
+            items: self.items.into_iter().map(LineItem::anonymize).collect(),
+            delivery: self.delivery.anonymize(),
+            anonymized: true,
+            ..self
+        }
+    }
 }
@@ -0,0 +1,146 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::newspaper::Year;
+
+const PREFIX: &str = "DN";
+/// The scrambled sequence is split in two halves of three digits.
+const HALF: u64 = 1_000;
+/// The sequences from 1 up to here get a number of six digits.
+const CAPACITY: u64 = HALF * HALF;
+const ROUNDS: u64 = 4;
+
+/// The secret of a deployment by which the sequences of the orders are scrambled.
+/// The component has no source of randomness, so it is derived from the time
+/// the first order is numbered.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct NumberKey(u64);
+
+impl NumberKey {
+    pub(crate) fn from_time(timestamp: u64) -> Self {
+        Self(mix(timestamp, 0))
+    }
+}
+
+/// The number the customer uses to refer to an order, e.g. "DN-2026-242129-8".
+/// Unlike the order key it does not reveal the time of the order,
+/// and the last digit catches most typos when it is read over the phone.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(transparent)]
+pub(crate) struct OrderNumber(String);
+
+impl OrderNumber {
+    /// The sequence is scrambled by a keyed permutation, a Feistel network over the two halves
+    /// of its digits, so that without the key a number does not give away the ones before
+    /// and after it. There is no number once the sequence does not fit in six digits.
+    pub(crate) fn new(year: Year, sequence: u64, key: &NumberKey) -> Option<Self> {
+        (sequence < CAPACITY).then(|| {
+            let scrambled = scramble(sequence, key);
+            let digits = format!("{:04}{:06}", year, scrambled);
+            Self(format!(
+                "{}-{:04}-{:06}-{}",
+                PREFIX,
+                year,
+                scrambled,
+                check_digit(&digits)
+            ))
+        })
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Each round is a bijection of the pair of halves, so no two sequences share a number.
+fn scramble(sequence: u64, key: &NumberKey) -> u64 {
+    let (left, right) = (0..ROUNDS).fold(
+        (sequence / HALF, sequence % HALF),
+        |(left, right), round| (right, (left + mix(key.0, round * HALF + right)) % HALF),
+    );
+    left * HALF + right
+}
+
+/// The finalizer of SplitMix64, a change of any bit of the input changes half of the output.
+fn mix(key: u64, value: u64) -> u64 {
+    let z = key.wrapping_add(value.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Luhn check digit, detects any single wrong digit and most swaps of adjacent digits.
+fn check_digit(digits: &str) -> u32 {
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, digit)| match (i % 2 == 0, digit * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => digit,
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{NumberKey, OrderNumber, CAPACITY};
+
+    // 29-11-2024
+    const NOW: u64 = 1732880395000;
+
+    #[test]
+    fn format() {
+        let number = OrderNumber::new(2026, 1, &NumberKey::from_time(NOW)).unwrap();
+        assert_eq!(number.as_str(), "DN-2026-242129-8");
+    }
+
+    #[test]
+    fn check_digit() {
+        assert_eq!(super::check_digit("7992739871"), 3);
+        assert_eq!(super::check_digit("2026738917"), 7);
+    }
+
+    #[test]
+    fn unique_numbers() {
+        let key = NumberKey::from_time(NOW);
+        let numbers: HashSet<u64> = (0..CAPACITY)
+            .map(|sequence| super::scramble(sequence, &key))
+            .collect();
+        assert_eq!(numbers.len() as u64, CAPACITY);
+        assert!(numbers.iter().all(|number| *number < CAPACITY));
+    }
+
+    #[test]
+    fn not_linear() {
+        let key = NumberKey::from_time(NOW);
+        let differences: HashSet<u64> = (1..100)
+            .map(|sequence| {
+                (super::scramble(sequence + 1, &key) + CAPACITY - super::scramble(sequence, &key))
+                    % CAPACITY
+            })
+            .collect();
+        assert!(differences.len() > 90);
+    }
+
+    #[test]
+    fn keyed() {
+        let first = OrderNumber::new(2026, 1, &NumberKey::from_time(NOW)).unwrap();
+        let second = OrderNumber::new(2026, 1, &NumberKey::from_time(NOW + 1)).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn last_number() {
+        let key = NumberKey::from_time(NOW);
+        assert!(OrderNumber::new(2026, CAPACITY - 1, &key).is_some());
+        assert_eq!(OrderNumber::new(2026, CAPACITY, &key), None);
+    }
+}
@@ -0,0 +1,216 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use std::{ops::RangeInclusive, result::Result as StdResult};
+
+use super::{Error, Result};
+
+/// E.164 limits the number of digits, excluding the leading '+', to 15.
+const E164_MAX_DIGITS: usize = 15;
+
+/// Prefix used for international calls instead of '+', e.g. "0049..."
+const INTERNATIONAL_PREFIX: &str = "00";
+
+/// Numbers dialed with the trunk prefix and no country code are Bulgarian.
+const HOME: &NumberingPlan = &BULGARIA;
+const TRUNK_PREFIX: &str = "0";
+
+/// The prefixes in the regular expressions of JSON Schema, the digits are checked by the plans.
+pub(super) const PATTERN: &str = r"^(\+|0)[0-9]+$";
+
+const BULGARIA: NumberingPlan = NumberingPlan {
+    calling_code: "359",
+    mobile_prefixes: &["87", "88", "89", "98", "99"],
+    mobile_digits: 9..=9,
+    // 8 in Sofia, its area code 2 followed by 7 digits, down to 6 in some villages
+    landline_digits: 6..=9,
+};
+
+const GERMANY: NumberingPlan = NumberingPlan {
+    calling_code: "49",
+    mobile_prefixes: &["15", "16", "17"],
+    mobile_digits: 10..=11,
+    landline_digits: 6..=11,
+};
+
+const UNITED_KINGDOM: NumberingPlan = NumberingPlan {
+    calling_code: "44",
+    mobile_prefixes: &["7"],
+    mobile_digits: 10..=10,
+    landline_digits: 9..=10,
+};
+
+const SPAIN: NumberingPlan = NumberingPlan {
+    calling_code: "34",
+    mobile_prefixes: &["6", "7"],
+    mobile_digits: 9..=9,
+    landline_digits: 9..=9,
+};
+
+/// The countries we deliver to. The list is fixed at build time, so accepting the numbers
+/// of another country takes a new release.
+pub(super) const SUPPORTED_COUNTRIES: &[NumberingPlan] =
+    &[BULGARIA, GERMANY, UNITED_KINGDOM, SPAIN];
+
+/// The per-country rules for the national significant number, i.e. the digits after the
+/// country code.
+pub(super) struct NumberingPlan {
+    calling_code: &'static str,
+    mobile_prefixes: &'static [&'static str],
+    mobile_digits: RangeInclusive<usize>,
+    landline_digits: RangeInclusive<usize>,
+}
+
+impl NumberingPlan {
+    fn line(&self, national: &str) -> Line {
+        if self
+            .mobile_prefixes
+            .iter()
+            .any(|prefix| national.starts_with(prefix))
+        {
+            Line::Mobile
+        } else {
+            Line::Landline
+        }
+    }
+
+    fn allowed_digits(&self, line: Line) -> &RangeInclusive<usize> {
+        match line {
+            Line::Mobile => &self.mobile_digits,
+            Line::Landline => &self.landline_digits,
+        }
+    }
+
+    fn check_national(&self, national: &str) -> Result<()> {
+        let line = self.line(national);
+        if national.chars().all(|c| c.is_ascii_digit())
+            && self.allowed_digits(line).contains(&national.len())
+            && self.calling_code.len() + national.len() <= E164_MAX_DIGITS
+        {
+            Ok(())
+        } else {
+            Err(Error::InvalidDelivery("Wrong number of digits"))
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Line {
+    Mobile,
+    Landline,
+}
+
+/// Parses a phone number written either in the Bulgarian national format, e.g. "0873528495",
+/// or in international format, e.g. "+359873528495" or "00359873528495", and returns it in
+/// canonical E.164 form, e.g. "+359873528495".
+pub(super) fn normalize(number: &str, plans: &[NumberingPlan]) -> Result<String> {
+    number
+        .strip_prefix('+')
+        .or_else(|| number.strip_prefix(INTERNATIONAL_PREFIX))
+        .map(|international| {
+            plans
+                .iter()
+                .find(|plan| international.starts_with(plan.calling_code))
+                .ok_or(unsupported_prefix())
+                .map(|plan| (plan, &international[plan.calling_code.len()..]))
+        })
+        .or_else(|| {
+            number
+                .strip_prefix(TRUNK_PREFIX)
+                .map(|national| Ok((HOME, national)))
+        })
+        .unwrap_or(Err(unsupported_prefix()))
+        .and_then(|(plan, national)| {
+            plan.check_national(national)
+                .map(|()| format!("+{}{}", plan.calling_code, national))
+        })
+}
+
+/// A phone number in canonical form, used to look up the orders of a customer.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, JsonSchema)]
+#[serde(try_from = "String")]
+#[schemars(extend("pattern" = PATTERN))]
+pub(crate) struct PhoneNumber(String);
+
+impl PhoneNumber {
+    pub(crate) fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl TryFrom<String> for PhoneNumber {
+    type Error = Error;
+
+    fn try_from(value: String) -> StdResult<Self, Self::Error> {
+        normalize(&value, SUPPORTED_COUNTRIES).map(Self)
+    }
+}
+
+fn unsupported_prefix() -> Error {
+    Error::InvalidDelivery(
+        "Phone number must start with 0 or +359, or with the code of a supported country",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize, Result, SUPPORTED_COUNTRIES};
+
+    #[test]
+    fn bulgarian_numbers() {
+        assert_eq!(parse("0873528495").unwrap(), "+359873528495");
+        assert_eq!(parse("+359873528495").unwrap(), "+359873528495");
+        assert_eq!(parse("00359873528495").unwrap(), "+359873528495");
+        assert_eq!(parse("+359461839203").unwrap(), "+359461839203");
+        assert_eq!(parse("029876543").unwrap(), "+35929876543");
+        assert_eq!(parse("+35932123456").unwrap(), "+35932123456");
+    }
+
+    #[test]
+    fn foreign_numbers() {
+        assert_eq!(parse("+4915123456789").unwrap(), "+4915123456789");
+        assert_eq!(parse("+49301234567").unwrap(), "+49301234567");
+        assert_eq!(parse("+447911123456").unwrap(), "+447911123456");
+        assert_eq!(parse("0034612345678").unwrap(), "+34612345678");
+    }
+
+    #[test]
+    fn mobile_and_landline_lengths() {
+        let msg = "Wrong number of digits";
+
+        // German mobile numbers have at least 10 digits, landlines may be shorter
+        assert_err(parse("+49151234567"), msg);
+        assert!(parse("+4930123456").is_ok());
+
+        // Bulgarian mobile numbers have exactly 9 digits, landlines may be shorter
+        assert_err(parse("08735284951"), msg);
+        assert_err(parse("087352849"), msg);
+        assert_err(parse("02987"), msg);
+
+        // British mobile numbers have exactly 10 digits
+        assert_err(parse("+4479111234567"), msg);
+        assert!(parse("+442071234567").is_ok());
+
+        // Spanish mobile numbers and landlines both have exactly 9 digits
+        assert!(parse("+34912345678").is_ok());
+        assert_err(parse("+3491234567"), msg);
+        assert_err(parse("+34612345"), msg);
+    }
+
+    #[test]
+    fn unsupported_country() {
+        let msg = "Phone number must start with 0 or +359";
+        assert_err(parse("+358873528495"), msg);
+        assert_err(parse("+1 2025550123"), msg);
+        assert_err(parse("873528495"), msg);
+    }
+
+    fn parse(number: &str) -> Result<String> {
+        normalize(number, SUPPORTED_COUNTRIES)
+    }
+
+    fn assert_err(r: Result<String>, msg: &str) {
+        assert!(r.expect_err("expected an error").to_string().contains(msg))
+    }
+}
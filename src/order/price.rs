@@ -0,0 +1,201 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::result::Result as StdResult;
+
+use crate::{repo::ConfigRepo, Storage};
+
+use super::{delivery::Priority, line_item::LineItem, Error, Result};
+
+/// The prices of the cards and of their delivery, in euro cents. An order is priced as a whole:
+/// the cards of all its lines count towards the bulk discount, and it is delivered once.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "UncheckedPriceList")]
+pub(crate) struct PriceList {
+    card_cents: u32,
+    standard_delivery_cents: u32,
+    express_delivery_cents: u32,
+    bulk_discount: Option<BulkDiscount>,
+}
+
+/// A discount on the cards of the orders holding at least `min_cards` cards.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(crate) struct BulkDiscount {
+    min_cards: u32,
+    percent: u8,
+}
+
+impl PriceList {
+    fn new(
+        card_cents: u32,
+        standard_delivery_cents: u32,
+        express_delivery_cents: u32,
+        bulk_discount: Option<BulkDiscount>,
+    ) -> Self {
+        Self {
+            card_cents,
+            standard_delivery_cents,
+            express_delivery_cents,
+            bulk_discount,
+        }
+    }
+
+    /// Fetches the stored price list, falling back to the default one if none is configured yet.
+    pub(super) fn load<S>(storage: &S) -> Result<Self>
+    where
+        S: Storage,
+    {
+        ConfigRepo::load(storage)
+            .map(Option::unwrap_or_default)
+            .map_err(Error::Storage)
+    }
+
+    pub(super) fn price(&self, items: &[LineItem], priority: &Priority) -> Price {
+        let cards: u64 = items.iter().map(|item| u64::from(item.quantity())).sum();
+        let cards_cents = cards * u64::from(self.card_cents);
+        let discount_cents = self
+            .bulk_discount
+            .as_ref()
+            .filter(|discount| cards >= u64::from(discount.min_cards))
+            .map_or(0, |discount| {
+                cards_cents * u64::from(discount.percent) / 100
+            });
+        let delivery_cents = u64::from(match priority {
+            Priority::Standart => self.standard_delivery_cents,
+            Priority::Express => self.express_delivery_cents,
+        });
+
+        Price {
+            cards_cents,
+            discount_cents,
+            delivery_cents,
+            total_cents: cards_cents - discount_cents + delivery_cents,
+        }
+    }
+
+    fn invariant_held(&self) -> Result<()> {
+        match &self.bulk_discount {
+            Some(discount) if discount.min_cards == 0 => Err(Error::InvalidPriceList(
+                "The bulk discount must start from at least 1 card",
+            )),
+            Some(discount) if discount.percent == 0 || discount.percent > 100 => Err(
+                Error::InvalidPriceList("The bulk discount must be between 1 and 100 percent"),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for PriceList {
+    /// A card for 5.00, delivered for 4.00, or for 9.00 as express, without a bulk discount.
+    fn default() -> Self {
+        Self::new(500, 400, 900, None)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "PriceList")]
+struct UncheckedPriceList {
+    card_cents: u32,
+    standard_delivery_cents: u32,
+    express_delivery_cents: u32,
+    #[serde(default)]
+    bulk_discount: Option<BulkDiscount>,
+}
+
+impl TryFrom<UncheckedPriceList> for PriceList {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedPriceList) -> StdResult<Self, Self::Error> {
+        let obj = Self::new(
+            unchecked.card_cents,
+            unchecked.standard_delivery_cents,
+            unchecked.express_delivery_cents,
+            unchecked.bulk_discount,
+        );
+        obj.invariant_held().map(|()| obj)
+    }
+}
+
+/// The price of an order as it was placed, in euro cents, kept along with the order
+/// so that a later change of the price list does not change it.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Price {
+    cards_cents: u64,
+    discount_cents: u64,
+    delivery_cents: u64,
+    total_cents: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::order::{delivery::Priority, line_item::LineItem, wish_card::WishCard};
+
+    use super::{BulkDiscount, Price, PriceList};
+
+    #[test]
+    fn priced_together() {
+        let prices = PriceList::new(500, 400, 900, None);
+        let items = [item(2), item(3)];
+
+        assert_eq!(
+            prices.price(&items, &Priority::Standart),
+            Price {
+                cards_cents: 2500,
+                discount_cents: 0,
+                delivery_cents: 400,
+                total_cents: 2900
+            }
+        );
+        assert_eq!(prices.price(&items, &Priority::Express).total_cents, 3400);
+    }
+
+    #[test]
+    fn bulk_discount() {
+        let discount = Some(BulkDiscount {
+            min_cards: 5,
+            percent: 10,
+        });
+        let prices = PriceList::new(500, 400, 900, discount);
+
+        // the cards of every line count towards the discount
+        let price = prices.price(&[item(2), item(3)], &Priority::Standart);
+        assert_eq!((price.discount_cents, price.total_cents), (250, 2650));
+
+        let price = prices.price(&[item(4)], &Priority::Standart);
+        assert_eq!((price.discount_cents, price.total_cents), (0, 2400));
+    }
+
+    #[test]
+    fn invalid_discount() {
+        for discount in [
+            r#"{"min_cards":0,"percent":10}"#,
+            r#"{"min_cards":5,"percent":101}"#,
+        ] {
+            let json = format!(
+                r#"{{"card_cents":500,"standard_delivery_cents":400,"express_delivery_cents":900,"bulk_discount":{}}}"#,
+                discount
+            );
+            assert!(serde_json::from_str::<PriceList>(&json)
+                .expect_err("expected the discount to be invalid")
+                .to_string()
+                .contains("Invalid price list"));
+        }
+
+        let json =
+            r#"{"card_cents":500,"standard_delivery_cents":400,"express_delivery_cents":900}"#;
+        assert_eq!(
+            serde_json::from_str::<PriceList>(json).unwrap(),
+            PriceList::default()
+        );
+    }
+
+    fn item(quantity: u8) -> LineItem {
+        let card = r#"{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10}"#;
+        let wish_card: WishCard = serde_json::from_str(card).expect("failed to deserialize JSON");
+        LineItem::new_unchecked(wish_card, quantity)
+    }
+}
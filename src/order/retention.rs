@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::newspaper::Date;
+
+use super::Order;
+
+/// How many days after the delivery the personal data of an order is kept.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct RetentionPeriod(u16);
+
+impl RetentionPeriod {
+    pub(crate) fn new(days: u16) -> Self {
+        Self(days)
+    }
+
+    pub(crate) fn expired(&self, order: &Order, today: &Date) -> bool {
+        !order.is_anonymized() && order.delivered_before(&today.days_before(self.0))
+    }
+}
@@ -0,0 +1,262 @@
+use chrono::{DateTime, FixedOffset, NaiveTime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::result::Result as StdResult;
+
+use crate::{newspaper::Date, repo::ConfigRepo, Storage};
+
+use super::{delivery::Priority, Error, Result};
+
+/// Configures when the orders are shipped and how long the delivery takes.
+/// Orders placed before the cut-off time on a business day are shipped the same day,
+/// all others are shipped on the next business day.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "UncheckedDeliverySchedule")]
+pub(crate) struct DeliverySchedule {
+    cutoff_hour: u8,
+    cutoff_minute: u8,
+    utc_offset_minutes: i16,
+    standard_days: u8,
+    express_days: u8,
+    holidays: Vec<Date>,
+}
+
+impl DeliverySchedule {
+    fn new(
+        cutoff_hour: u8,
+        cutoff_minute: u8,
+        utc_offset_minutes: i16,
+        standard_days: u8,
+        express_days: u8,
+        holidays: Vec<Date>,
+    ) -> Self {
+        Self {
+            cutoff_hour,
+            cutoff_minute,
+            utc_offset_minutes,
+            standard_days,
+            express_days,
+            holidays,
+        }
+    }
+
+    /// Fetches the stored schedule, falling back to the default one if none is configured yet.
+    pub(super) fn load<S>(storage: &S) -> Result<Self>
+    where
+        S: Storage,
+    {
+        ConfigRepo::load(storage)
+            .map(Option::unwrap_or_default)
+            .map_err(Error::Storage)
+    }
+
+    pub(super) fn estimate(&self, now_ms: u64, priority: &Priority) -> Estimate {
+        let local = self.local_time(now_ms);
+        let today = Date::from(local.date_naive());
+        let cutoff = NaiveTime::from_hms_opt(
+            u32::from(self.cutoff_hour),
+            u32::from(self.cutoff_minute),
+            0,
+        )
+        .expect("the cut-off time is checked on creation");
+
+        let ship_on = if self.is_business_day(&today) && local.time() < cutoff {
+            today
+        } else {
+            self.next_business_day(today)
+        };
+        let transit_days = match priority {
+            Priority::Standart => self.standard_days,
+            Priority::Express => self.express_days,
+        };
+        let deliver_on = (0..transit_days).fold(ship_on, |date, _| self.next_business_day(date));
+
+        Estimate {
+            ship_on,
+            deliver_on,
+        }
+    }
+
+    fn local_time(&self, now_ms: u64) -> DateTime<FixedOffset> {
+        let offset = FixedOffset::east_opt(i32::from(self.utc_offset_minutes) * 60)
+            .expect("the utc offset is checked on creation");
+        DateTime::from_timestamp_millis(
+            i64::try_from(now_ms).expect("u64 value is too large for i64"),
+        )
+        .expect("invalid timestamp")
+        .with_timezone(&offset)
+    }
+
+    fn is_business_day(&self, date: &Date) -> bool {
+        !date.is_weekend() && !self.holidays.contains(date)
+    }
+
+    fn next_business_day(&self, date: Date) -> Date {
+        let mut next = date.next_day();
+        while !self.is_business_day(&next) {
+            next = next.next_day();
+        }
+        next
+    }
+
+    fn invariant_held(&self) -> Result<()> {
+        if NaiveTime::from_hms_opt(
+            u32::from(self.cutoff_hour),
+            u32::from(self.cutoff_minute),
+            0,
+        )
+        .is_none()
+        {
+            Err(Error::InvalidSchedule(
+                "The cut-off time is not a valid time",
+            ))
+        } else if i32::from(self.utc_offset_minutes).abs() >= 24 * 60 {
+            Err(Error::InvalidSchedule(
+                "The utc offset must be less than a day",
+            ))
+        } else if self.express_days > self.standard_days {
+            Err(Error::InvalidSchedule(
+                "Express delivery cannot take longer than standard delivery",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for DeliverySchedule {
+    /// Ships the orders placed before 14:00 Bulgarian (EET) time.
+    fn default() -> Self {
+        Self::new(14, 0, 120, 3, 1, Vec::new())
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct UncheckedDeliverySchedule {
+    cutoff_hour: u8,
+    cutoff_minute: u8,
+    utc_offset_minutes: i16,
+    standard_days: u8,
+    express_days: u8,
+    #[serde(default)]
+    holidays: Vec<Date>,
+}
+
+impl TryFrom<UncheckedDeliverySchedule> for DeliverySchedule {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedDeliverySchedule) -> StdResult<Self, Self::Error> {
+        let obj = Self::new(
+            unchecked.cutoff_hour,
+            unchecked.cutoff_minute,
+            unchecked.utc_offset_minutes,
+            unchecked.standard_days,
+            unchecked.express_days,
+            unchecked.holidays,
+        );
+        obj.invariant_held().map(|()| obj)
+    }
+}
+
+/// The estimated dates when the order leaves the warehouse and reaches the customer.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub(super) struct Estimate {
+    ship_on: Date,
+    deliver_on: Date,
+}
+
+impl Estimate {
+    pub(crate) fn deliver_on(&self) -> &Date {
+        &self.deliver_on
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{newspaper::Date, order::delivery::Priority};
+
+    use super::{DeliverySchedule, Estimate, Result};
+
+    // 29-11-2024 (Friday) 11:39:55 UTC, 13:39:55 in Bulgaria
+    const FRIDAY_BEFORE_CUTOFF: u64 = 1732880395000;
+    // 29-11-2024 (Friday) 12:30:00 UTC, 14:30:00 in Bulgaria
+    const FRIDAY_AFTER_CUTOFF: u64 = 1732883400000;
+    // 30-11-2024 (Saturday) 08:00:00 UTC
+    const SATURDAY: u64 = 1732953600000;
+
+    #[test]
+    fn before_cutoff() {
+        let schedule = DeliverySchedule::default();
+        assert_eq!(
+            schedule.estimate(FRIDAY_BEFORE_CUTOFF, &Priority::Express),
+            estimate(Date::new(29, 11, 2024), Date::new(2, 12, 2024))
+        );
+        assert_eq!(
+            schedule.estimate(FRIDAY_BEFORE_CUTOFF, &Priority::Standart),
+            estimate(Date::new(29, 11, 2024), Date::new(4, 12, 2024))
+        );
+    }
+
+    #[test]
+    fn after_cutoff() {
+        let schedule = DeliverySchedule::default();
+        assert_eq!(
+            schedule.estimate(FRIDAY_AFTER_CUTOFF, &Priority::Express),
+            estimate(Date::new(2, 12, 2024), Date::new(3, 12, 2024))
+        );
+        assert_eq!(
+            schedule.estimate(SATURDAY, &Priority::Express),
+            estimate(Date::new(2, 12, 2024), Date::new(3, 12, 2024))
+        );
+    }
+
+    #[test]
+    fn holidays() {
+        let schedule = DeliverySchedule::new(14, 0, 120, 3, 1, vec![Date::new(2, 12, 2024)]);
+        assert_eq!(
+            schedule.estimate(FRIDAY_AFTER_CUTOFF, &Priority::Express),
+            estimate(Date::new(3, 12, 2024), Date::new(4, 12, 2024))
+        );
+    }
+
+    #[test]
+    fn deserialization() {
+        let json = r#"{"cutoff_hour":12,"cutoff_minute":30,"utc_offset_minutes":180,"standard_days":4,"express_days":1,"holidays":["24-12-2024"]}"#;
+        let schedule: DeliverySchedule =
+            serde_json::from_str(json).expect("failed to deserialize JSON");
+        assert_eq!(
+            schedule,
+            DeliverySchedule::new(12, 30, 180, 4, 1, vec![Date::new(24, 12, 2024)])
+        );
+    }
+
+    #[test]
+    fn invalid_schedule() {
+        assert_err(
+            DeliverySchedule::new(24, 0, 120, 3, 1, Vec::new()).invariant_held(),
+            "The cut-off time is not a valid time",
+        );
+        assert_err(
+            DeliverySchedule::new(14, 0, 1440, 3, 1, Vec::new()).invariant_held(),
+            "The utc offset must be less than a day",
+        );
+        assert_err(
+            DeliverySchedule::new(14, 0, 120, 1, 2, Vec::new()).invariant_held(),
+            "Express delivery cannot take longer than standard delivery",
+        );
+    }
+
+    fn estimate(ship_on: Date, deliver_on: Date) -> Estimate {
+        Estimate {
+            ship_on,
+            deliver_on,
+        }
+    }
+
+    fn assert_err(r: Result<()>, msg: &str) {
+        assert!(r.expect_err("expected an error").to_string().contains(msg))
+    }
+}
@@ -0,0 +1,34 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::{delivery::Priority, schedule::Estimate, Order, OrderNumber, Price};
+
+/// What the consumers of the events need to know about a placed order,
+/// without the personal data of the customer.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct OrderSummary {
+    number: Option<OrderNumber>,
+    cards: u32,
+    priority: Priority,
+    estimate: Estimate,
+    created_on_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<Price>,
+}
+
+impl From<&Order> for OrderSummary {
+    fn from(order: &Order) -> Self {
+        Self {
+            number: order.number.clone(),
+            cards: order
+                .items
+                .iter()
+                .map(|item| u32::from(item.quantity()))
+                .sum(),
+            priority: order.delivery.priority().clone(),
+            estimate: order.estimate.clone(),
+            created_on_ms: order.created_on_ms,
+            price: order.price.clone(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    delivery::Delivery, line_item::LineItem, schedule::DeliverySchedule, wish_card::WishCard, Order,
+};
+
+/// The first orders had a single wish card and no delivery estimate.
+#[derive(Deserialize)]
+struct OrderV1 {
+    wish_card: WishCard,
+    delivery: Delivery,
+    created_on_ms: u64,
+}
+
+/// Turns the single wish card into a line item ordered once and estimates the delivery
+/// with the default schedule, the one in effect when these orders were placed.
+/// Orders already holding line items were written unversioned by later releases
+/// and are left as they are.
+pub(crate) fn to_line_items(value: Value) -> serde_json::Result<Value> {
+    if value.get("items").is_some() {
+        return Ok(value);
+    }
+
+    serde_json::from_value::<OrderV1>(value).and_then(|order| {
+        let estimate =
+            DeliverySchedule::default().estimate(order.created_on_ms, order.delivery.priority());
+        serde_json::to_value(Order::new_unchecked(
+            vec![LineItem::new_unchecked(order.wish_card, 1)],
+            order.delivery,
+            estimate,
+            order.created_on_ms,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{newspaper::Date, order::Order};
+
+    const ORDER_V1: &str = r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"},"created_on_ms":1732880395000}"#;
+
+    #[test]
+    fn single_wish_card() {
+        let value = serde_json::from_str(ORDER_V1).unwrap();
+        let order: Order = serde_json::from_value(super::to_line_items(value).unwrap())
+            .expect("the upgraded order is readable");
+
+        assert_eq!(order.identifier(), "1732880395000_0873528495");
+        assert!(!order.delivered_before(&Date::new(4, 12, 2024)));
+        assert!(order.delivered_before(&Date::new(5, 12, 2024)));
+    }
+
+    #[test]
+    fn already_upgraded() {
+        let value = serde_json::from_str(ORDER_V1).unwrap();
+        let upgraded = super::to_line_items(value).unwrap();
+        assert_eq!(super::to_line_items(upgraded.clone()).unwrap(), upgraded);
+    }
+}
@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use std::{marker::PhantomData, result::Result as StdResult};
 
-use crate::Storage;
+use crate::{repo::ConfigRepo, Storage};
 
 use super::{
     cover::{Cover, UncheckedCover},
@@ -10,7 +11,7 @@ use super::{
 };
 
 #[cfg_attr(test, derive(Debug, PartialEq))]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub(super) struct WishCard {
     covers: Cover,
     background: Rgb,
@@ -42,6 +43,18 @@ impl WishCard {
         }
     }
 
+    /// The message is personal, unlike the appearance of the card.
+    pub(super) fn anonymize(self) -> Self {
+        Self {
+            message: String::new(),
+            ..self
+        }
+    }
+
+    pub(super) fn signatures(&self) -> impl Iterator<Item = &str> {
+        self.covers.signatures()
+    }
+
     pub(super) fn invariant_held(&self, max_cards: MaxCards) -> Result<()> {
         if self.template_id > max_cards.0 {
             Err(Error::InvalidCard)
@@ -65,7 +78,7 @@ impl MaxCards {
 }
 
 // Since Rbg only accepts `u8` values, there is no need to check the invariant
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub(super) struct Rgb([u8; 3]);
 
@@ -76,7 +89,7 @@ impl Rgb {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub(super) enum Frame {
     White,
@@ -84,8 +97,9 @@ pub(super) enum Frame {
     Wooden,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(bound = "")]
+#[schemars(bound = "", rename = "WishCard")]
 pub(super) struct UncheckedWishCard<S>
 where
     S: Storage + Default,
@@ -105,8 +119,28 @@ impl<S> UncheckedWishCard<S>
 where
     S: Storage + Default,
 {
-    fn into_checked(self) -> Result<WishCard> {
-        self.covers.try_into().map(|covers| {
+    /// Checks the card against the catalog and the settings of the given storage instead
+    /// of the ones of `S`.
+    pub(super) fn check<St>(self, storage: &St) -> Result<WishCard>
+    where
+        St: Storage,
+    {
+        ConfigRepo::load::<_, MaxCards>(storage)
+            .map_err(Error::Storage)
+            .and_then(|max_cards| {
+                max_cards.ok_or(Error::NotFound("Failed to fetch the max cards.".into()))
+            })
+            .and_then(|max_cards| {
+                self.into_checked(storage)
+                    .and_then(|obj| obj.invariant_held(max_cards).map(|()| obj))
+            })
+    }
+
+    fn into_checked<St>(self, storage: &St) -> Result<WishCard>
+    where
+        St: Storage,
+    {
+        self.covers.check(storage).map(|covers| {
             WishCard::new_unchecked(
                 covers,
                 self.background,
@@ -127,18 +161,7 @@ where
     type Error = Error;
 
     fn try_from(unchecked: UncheckedWishCard<S>) -> StdResult<Self, Self::Error> {
-        S::default()
-            .retrieve("max_cards")
-            .ok_or(Error::NotFound("Failed to fetch the max cards.".into()))
-            .and_then(|max_cards| {
-                serde_json::from_slice(&max_cards)
-                    .map_err(Error::DeserializationFault)
-                    .and_then(|max_cards| {
-                        unchecked
-                            .into_checked()
-                            .and_then(|obj| obj.invariant_held(max_cards).map(|()| obj))
-                    })
-            })
+        unchecked.check(&S::default())
     }
 }
 
@@ -0,0 +1,112 @@
+use crate::{
+    newspaper::DateFormat,
+    order::{DeliverySchedule, MaxCards, NumberKey, PriceList, RetentionPeriod},
+    Storage,
+};
+
+use super::{Migration, Record, Result, CONFIG, COUNTERS};
+
+/// A setting stored under its own name in the config namespace.
+pub(crate) trait Config: Record {
+    const NAME: &'static str;
+}
+
+impl Record for MaxCards {}
+
+impl Record for DeliverySchedule {}
+
+impl Record for RetentionPeriod {}
+
+impl Record for NumberKey {}
+
+impl Record for DateFormat {}
+
+impl Record for PriceList {}
+
+impl Config for MaxCards {
+    const NAME: &'static str = "max_cards";
+}
+
+impl Config for DeliverySchedule {
+    const NAME: &'static str = "delivery_schedule";
+}
+
+impl Config for RetentionPeriod {
+    const NAME: &'static str = "retention_days";
+}
+
+impl Config for NumberKey {
+    const NAME: &'static str = "order_number_key";
+}
+
+impl Config for DateFormat {
+    const NAME: &'static str = "date_format";
+}
+
+impl Config for PriceList {
+    const NAME: &'static str = "price_list";
+}
+
+pub(crate) struct ConfigRepo;
+
+impl ConfigRepo {
+    pub(crate) fn load<S, C>(storage: &S) -> Result<Option<C>>
+    where
+        S: Storage,
+        C: Config,
+    {
+        CONFIG.load(storage, C::NAME)
+    }
+
+    pub(crate) fn save<S, C>(storage: &mut S, config: &C) -> Result<()>
+    where
+        S: Storage,
+        C: Config,
+    {
+        CONFIG.save(storage, C::NAME, config)
+    }
+
+    pub(crate) fn migrate<S>(storage: &mut S) -> Migration
+    where
+        S: Storage,
+    {
+        [
+            CONFIG.migrate::<_, MaxCards>(storage, MaxCards::NAME),
+            CONFIG.migrate::<_, DeliverySchedule>(storage, DeliverySchedule::NAME),
+            CONFIG.migrate::<_, RetentionPeriod>(storage, RetentionPeriod::NAME),
+            CONFIG.migrate::<_, NumberKey>(storage, NumberKey::NAME),
+            CONFIG.migrate::<_, DateFormat>(storage, DateFormat::NAME),
+            CONFIG.migrate::<_, PriceList>(storage, PriceList::NAME),
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+/// Monotonic counters, starting from 1.
+pub(crate) struct CounterRepo;
+
+impl CounterRepo {
+    pub(crate) const ORDERS: &'static str = "order";
+    pub(crate) const AUDIT: &'static str = "audit";
+
+    pub(crate) fn next<S>(storage: &mut S, name: &str) -> Result<u64>
+    where
+        S: Storage,
+    {
+        COUNTERS
+            .load::<_, u64>(storage, name)
+            .map(|counter| counter.unwrap_or(0) + 1)
+            .and_then(|next| COUNTERS.save(storage, name, &next).map(|()| next))
+    }
+
+    pub(crate) fn migrate<S>(storage: &mut S) -> Migration
+    where
+        S: Storage,
+    {
+        [Self::ORDERS, Self::AUDIT]
+            .into_iter()
+            .map(|name| COUNTERS.migrate::<_, u64>(storage, name))
+            .collect()
+    }
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    #[error("[Storage] Problem while serialization: {0}")]
+    SerializationFault(serde_json::Error),
+
+    #[error("[Storage] Problem while deserialization: {0}")]
+    DeserializationFault(serde_json::Error),
+
+    #[error("[Storage] The record has version {0}, which this contract does not know")]
+    UnknownVersion(u32),
+
+    #[error("[Storage] Cannot upgrade the record from version {0}: {1}")]
+    UpgradeFault(u32, serde_json::Error),
+
+    #[error("[Storage] Cannot find the key of the order, its number is not indexed")]
+    UnknownOrderKey,
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
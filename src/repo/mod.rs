@@ -0,0 +1,534 @@
+use std::{collections::HashSet, result::Result as StdResult};
+
+use crate::{bindings::ByteArray, newspaper::next_letter, Storage};
+
+mod config;
+mod error;
+mod newspaper;
+mod order;
+mod version;
+
+pub(crate) use config::{Config, ConfigRepo, CounterRepo};
+pub(crate) use error::{Error, Result};
+pub(crate) use newspaper::NewspaperRepo;
+pub(crate) use order::OrderRepo;
+pub(crate) use version::{Record, Upgrade};
+
+/// The outcome of rewriting the records of a namespace in their latest version.
+#[derive(Default)]
+pub(crate) struct Migration {
+    pub(crate) migrated: u32,
+    pub(crate) failed: u32,
+}
+
+impl Migration {
+    pub(crate) fn add(self, other: Self) -> Self {
+        Self {
+            migrated: self.migrated + other.migrated,
+            failed: self.failed + other.failed,
+        }
+    }
+}
+
+/// Each item tells whether a record was rewritten, or why it could not be.
+impl FromIterator<Result<bool>> for Migration {
+    fn from_iter<I: IntoIterator<Item = Result<bool>>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |migration, outcome| match outcome {
+                Ok(migrated) => Self {
+                    migrated: migration.migrated + u32::from(migrated),
+                    ..migration
+                },
+                Err(_) => Self {
+                    failed: migration.failed + 1,
+                    ..migration
+                },
+            })
+    }
+}
+
+/// Separates the name of a namespace from the id of a record, e.g. "newspaper/В1612".
+const SEPARATOR: char = '/';
+const AFTER_SEPARATOR: char = next_letter(SEPARATOR);
+
+pub(crate) const NEWSPAPERS: Namespace = Namespace::new(
+    "newspaper",
+    Some(Legacy::new(legacy_bare_key, Some(("В", "Г")))),
+);
+pub(crate) const ORDERS: Namespace = Namespace::new(
+    "order",
+    Some(Legacy::new(legacy_bare_key, Some(("0", ":")))),
+);
+pub(crate) const ORDER_NUMBERS: Namespace = Namespace::new(
+    "order_number",
+    Some(Legacy::new(
+        |id| format!("order_number_{}", id),
+        Some(("order_number_", "order_number`")),
+    )),
+);
+pub(crate) const IDEMPOTENCY: Namespace = Namespace::new(
+    "idempotency",
+    Some(Legacy::new(|id| format!("idempotency_{}", id), None)),
+);
+pub(crate) const CONFIG: Namespace =
+    Namespace::new("config", Some(Legacy::new(legacy_bare_key, None)));
+pub(crate) const AUDIT: Namespace = Namespace::new("audit", None);
+pub(crate) const COUNTERS: Namespace = Namespace::new(
+    "counter",
+    Some(Legacy::new(|id| format!("{}_counter", id), None)),
+);
+
+/// A record that cannot be deserialized, identified by the fingerprint of its contents,
+/// as in the audit log, since its key is not known and its contents may hold personal data.
+pub(crate) struct Unreadable {
+    pub(crate) fingerprint: String,
+    pub(crate) error: Error,
+}
+
+/// A group of records of the same kind. Each record is stored under "{namespace}/{id}",
+/// so that ids of different kinds never collide in the flat key space of the storage.
+pub(crate) struct Namespace {
+    name: &'static str,
+    legacy: Option<Legacy>,
+}
+
+/// Where the records were stored before the namespaces were introduced.
+/// The storage cannot delete or rename keys, so these records are still read when
+/// there is no namespaced copy, and kept in sync on every write.
+struct Legacy {
+    key: fn(&str) -> String,
+    range: Option<(&'static str, &'static str)>,
+}
+
+impl Legacy {
+    const fn new(key: fn(&str) -> String, range: Option<(&'static str, &'static str)>) -> Self {
+        Self { key, range }
+    }
+}
+
+/// The 64-bit FNV-1a hash, which is stable across releases, unlike the hasher of the std.
+pub(crate) fn fingerprint(bytes: &[u8]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let hash = bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Reads a record as it is stored, e.g. in a snapshot, upgrading it to the latest version.
+pub(crate) fn decode<V>(ser: &[u8]) -> Result<V>
+where
+    V: Record,
+{
+    version::decode(ser).map(|decoded| decoded.value)
+}
+
+/// The legacy records are kept in sync with their namespaced copies, so the ones with
+/// identical contents are the same record. The removed records are left out.
+fn with_legacy(mut records: Vec<ByteArray>, legacy: Vec<ByteArray>) -> Vec<ByteArray> {
+    let namespaced: HashSet<ByteArray> = records.iter().cloned().collect();
+    records.extend(
+        legacy
+            .into_iter()
+            .filter(|record| !namespaced.contains(record)),
+    );
+    records.retain(|record| !record.is_empty());
+    records
+}
+
+fn legacy_bare_key(id: &str) -> String {
+    id.to_string()
+}
+
+impl Namespace {
+    const fn new(name: &'static str, legacy: Option<Legacy>) -> Self {
+        Self { name, legacy }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(crate) fn key(&self, id: &str) -> String {
+        format!("{}{}{}", self.name, SEPARATOR, id)
+    }
+
+    pub(crate) fn contains<S>(&self, storage: &S, id: &str) -> bool
+    where
+        S: Storage,
+    {
+        self.retrieve(storage, id).is_some()
+    }
+
+    /// Records stored in an older version are upgraded on the fly.
+    pub(crate) fn load<S, V>(&self, storage: &S, id: &str) -> Result<Option<V>>
+    where
+        S: Storage,
+        V: Record,
+    {
+        self.retrieve(storage, id)
+            .map(|ser| version::decode(&ser).map(|decoded| decoded.value))
+            .transpose()
+    }
+
+    pub(crate) fn save<S, V>(&self, storage: &mut S, id: &str, value: &V) -> Result<()>
+    where
+        S: Storage,
+        V: Record,
+    {
+        version::encode(value).map(|ser| self.persist(storage, id, &ser))
+    }
+
+    /// Every record in the namespace, each deserialized on its own,
+    /// so that a broken record does not hide the rest.
+    pub(crate) fn load_all<S, V>(&self, storage: &S) -> Vec<Result<V>>
+    where
+        S: Storage,
+        V: Record,
+    {
+        self.retrieve_all(storage)
+            .into_iter()
+            .map(|ser| version::decode(&ser).map(|decoded| decoded.value))
+            .collect()
+    }
+
+    /// The records with ids from `start` to `end`, exclusive. The ids are compared as strings.
+    pub(crate) fn load_range<S, V>(&self, storage: &S, start: &str, end: &str) -> Vec<Result<V>>
+    where
+        S: Storage,
+        V: Record,
+    {
+        let records = storage.retrieve_range(&self.key(start), &self.key(end));
+        let legacy = self
+            .legacy
+            .as_ref()
+            .filter(|legacy| legacy.range.is_some())
+            .map(|legacy| storage.retrieve_range(&(legacy.key)(start), &(legacy.key)(end)))
+            .unwrap_or_default();
+        with_legacy(records, legacy)
+            .into_iter()
+            .map(|ser| version::decode(&ser).map(|decoded| decoded.value))
+            .collect()
+    }
+
+    /// The fingerprint of the stored record, to tell whether it has changed.
+    pub(crate) fn fingerprint<S>(&self, storage: &S, id: &str) -> Option<String>
+    where
+        S: Storage,
+    {
+        self.retrieve(storage, id).map(|ser| fingerprint(&ser))
+    }
+
+    /// Every record in the namespace, with a fingerprint of the ones that cannot be read,
+    /// since their ids are not known from the range.
+    pub(crate) fn inspect<S, V>(&self, storage: &S) -> Vec<StdResult<V, Unreadable>>
+    where
+        S: Storage,
+        V: Record,
+    {
+        self.retrieve_all(storage)
+            .into_iter()
+            .map(|ser| {
+                version::decode(&ser)
+                    .map(|decoded| decoded.value)
+                    .map_err(|error| Unreadable {
+                        fingerprint: fingerprint(&ser),
+                        error,
+                    })
+            })
+            .collect()
+    }
+
+    /// The records stored in an older version, already upgraded to the latest one.
+    /// The ids are not known from the range, so the caller saves them back under their ids.
+    pub(crate) fn load_outdated<S, V>(&self, storage: &S) -> Vec<Result<V>>
+    where
+        S: Storage,
+        V: Record,
+    {
+        self.retrieve_all(storage)
+            .into_iter()
+            .filter_map(|ser| match version::decode(&ser) {
+                Ok(decoded) => decoded.outdated.then_some(Ok(decoded.value)),
+                Err(error) => Some(Err(error)),
+            })
+            .collect()
+    }
+
+    /// Rewrites the record in the latest version, returns whether it was outdated.
+    pub(crate) fn migrate<S, V>(&self, storage: &mut S, id: &str) -> Result<bool>
+    where
+        S: Storage,
+        V: Record,
+    {
+        match self
+            .retrieve(storage, id)
+            .map(|ser| version::decode::<V>(&ser))
+        {
+            Some(Ok(decoded)) if decoded.outdated => {
+                self.save(storage, id, &decoded.value).map(|()| true)
+            }
+            Some(Err(error)) => Err(error),
+            _ => Ok(false),
+        }
+    }
+
+    /// The records with ids from `from` on as they are stored, without deserializing them.
+    pub(crate) fn dump_from<S>(&self, storage: &S, from: &str) -> Vec<ByteArray>
+    where
+        S: Storage,
+    {
+        self.dump_between(storage, from, None)
+    }
+
+    /// The records with ids from `from` up to `to`, exclusive, or up to the end of the namespace
+    /// if there is no `to`, as they are stored.
+    pub(crate) fn dump_between<S>(
+        &self,
+        storage: &S,
+        from: &str,
+        to: Option<&str>,
+    ) -> Vec<ByteArray>
+    where
+        S: Storage,
+    {
+        let end = to.map_or_else(|| self.end(), |to| self.key(to));
+        let legacy = self
+            .legacy
+            .as_ref()
+            .and_then(|legacy| {
+                legacy.range.map(|(start, end)| {
+                    let end =
+                        to.map_or(end.to_string(), |to| (legacy.key)(to).min(end.to_string()));
+                    ((legacy.key)(from).max(start.to_string()), end)
+                })
+            })
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| storage.retrieve_range(&start, &end))
+            .unwrap_or_default();
+        with_legacy(storage.retrieve_range(&self.key(from), &end), legacy)
+    }
+
+    /// Persists a record as it was stored, e.g. in a snapshot, without encoding it again.
+    pub(crate) fn restore<S>(&self, storage: &mut S, id: &str, ser: &ByteArray)
+    where
+        S: Storage,
+    {
+        self.persist(storage, id, ser)
+    }
+
+    /// Removes the record along with its legacy copy. The storage cannot delete keys,
+    /// so the record is overwritten with an empty value, which is read as missing.
+    pub(crate) fn remove<S>(&self, storage: &mut S, id: &str)
+    where
+        S: Storage,
+    {
+        self.persist(storage, id, &ByteArray::new())
+    }
+
+    /// The record as it is stored, under its namespaced key or else under its legacy one.
+    pub(crate) fn retrieve<S>(&self, storage: &S, id: &str) -> Option<ByteArray>
+    where
+        S: Storage,
+    {
+        storage
+            .retrieve(&self.key(id))
+            .or_else(|| {
+                self.legacy
+                    .as_ref()
+                    .and_then(|legacy| storage.retrieve(&(legacy.key)(id)))
+            })
+            .filter(|ser| !ser.is_empty())
+    }
+
+    fn persist<S>(&self, storage: &mut S, id: &str, value: &ByteArray)
+    where
+        S: Storage,
+    {
+        storage.persist(&self.key(id), value);
+        if let Some(legacy_key) = self.legacy.as_ref().map(|legacy| (legacy.key)(id)) {
+            if storage.retrieve(&legacy_key).is_some() {
+                storage.persist(&legacy_key, value);
+            }
+        }
+    }
+
+    fn retrieve_all<S>(&self, storage: &S) -> Vec<ByteArray>
+    where
+        S: Storage,
+    {
+        self.dump_from(storage, "")
+    }
+
+    /// The end of the keys starting with "{namespace}/", exclusive.
+    fn end(&self) -> String {
+        format!("{}{}", self.name, AFTER_SEPARATOR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{services::MockHost, Storage};
+
+    use super::{Legacy, Namespace};
+
+    const ITEMS: Namespace = Namespace::new("item", None);
+    const LEGACY_ITEMS: Namespace = Namespace::new(
+        "legacy_item",
+        Some(Legacy::new(super::legacy_bare_key, Some(("a", "b")))),
+    );
+
+    #[test]
+    fn removed_records() {
+        let mut storage = MockHost::default();
+        LEGACY_ITEMS.save(&mut storage, "a1", &1u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a2", &2u64).unwrap();
+        storage.persist("a1", &b"1".to_vec());
+
+        LEGACY_ITEMS.remove(&mut storage, "a1");
+        assert!(!LEGACY_ITEMS.contains(&storage, "a1"));
+        assert_eq!(storage.retrieve("a1"), Some(Vec::new()));
+        assert_eq!(LEGACY_ITEMS.load_all::<_, u64>(&storage).len(), 1);
+        assert_eq!(
+            LEGACY_ITEMS.load_range::<_, u64>(&storage, "a", "b").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn dump_from() {
+        let mut storage = MockHost::default();
+        LEGACY_ITEMS.save(&mut storage, "a1", &1u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a3", &3u64).unwrap();
+        storage.persist("a2", &b"2".to_vec());
+        storage.persist("a4", &b"4".to_vec());
+
+        assert_eq!(LEGACY_ITEMS.dump_from(&storage, "").len(), 4);
+        let mut records = LEGACY_ITEMS.dump_from(&storage, "a2");
+        records.sort();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], b"2".to_vec());
+        assert!(LEGACY_ITEMS.dump_from(&storage, "b").is_empty());
+    }
+
+    #[test]
+    fn dump_between() {
+        let mut storage = MockHost::default();
+        LEGACY_ITEMS.save(&mut storage, "a1", &1u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a3", &3u64).unwrap();
+        storage.persist("a2", &b"2".to_vec());
+        storage.persist("a4", &b"4".to_vec());
+
+        let mut records = LEGACY_ITEMS.dump_between(&storage, "a2", Some("a4"));
+        records.sort();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], b"2".to_vec());
+        assert_eq!(
+            LEGACY_ITEMS.dump_between(&storage, "a1", Some("a2")).len(),
+            1
+        );
+        assert!(LEGACY_ITEMS
+            .dump_between(&storage, "a2", Some("a2"))
+            .is_empty());
+    }
+
+    #[test]
+    fn prefixed_keys() {
+        let mut storage = MockHost::default();
+        ITEMS.save(&mut storage, "a1", &1u64).unwrap();
+
+        assert_eq!(ITEMS.key("a1"), "item/a1");
+        assert!(storage.retrieve("item/a1").is_some());
+        assert!(storage.retrieve("a1").is_none());
+        assert_eq!(ITEMS.load::<_, u64>(&storage, "a1").unwrap(), Some(1));
+    }
+
+    #[test]
+    fn range_of_namespace() {
+        let mut storage = MockHost::default();
+        ITEMS.save(&mut storage, "a1", &1u64).unwrap();
+        ITEMS.save(&mut storage, "a2", &2u64).unwrap();
+        storage.persist("item0", &serde_json::to_vec(&3).unwrap());
+        storage.persist("items/a3", &serde_json::to_vec(&4).unwrap());
+
+        let mut all: Vec<u64> = ITEMS
+            .load_all(&storage)
+            .into_iter()
+            .map(|res| res.unwrap())
+            .collect();
+        all.sort();
+        assert_eq!(all, vec![1, 2]);
+    }
+
+    #[test]
+    fn legacy_records() {
+        let mut storage = MockHost::default();
+        storage.persist("a1", &serde_json::to_vec(&1).unwrap());
+        storage.persist("a2", &serde_json::to_vec(&2).unwrap());
+
+        assert_eq!(
+            LEGACY_ITEMS.load::<_, u64>(&storage, "a1").unwrap(),
+            Some(1)
+        );
+
+        LEGACY_ITEMS.save(&mut storage, "a1", &10u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a3", &3u64).unwrap();
+        assert_eq!(
+            storage.retrieve("a1"),
+            Some(br#"{"version":1,"data":10}"#.to_vec())
+        );
+        assert!(storage.retrieve("a3").is_none());
+
+        let mut all: Vec<u64> = LEGACY_ITEMS
+            .load_all(&storage)
+            .into_iter()
+            .map(|res| res.unwrap())
+            .collect();
+        all.sort();
+        assert_eq!(all, vec![2, 3, 10]);
+    }
+
+    #[test]
+    fn range_with_legacy() {
+        let mut storage = MockHost::default();
+        storage.persist("a1", &serde_json::to_vec(&1).unwrap());
+        storage.persist("a5", &serde_json::to_vec(&5).unwrap());
+        LEGACY_ITEMS.save(&mut storage, "a1", &10u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a3", &3u64).unwrap();
+        LEGACY_ITEMS.save(&mut storage, "a4", &4u64).unwrap();
+
+        let mut range: Vec<u64> = LEGACY_ITEMS
+            .load_range(&storage, "a1", "a4")
+            .into_iter()
+            .map(|res| res.unwrap())
+            .collect();
+        range.sort();
+        assert_eq!(range, vec![3, 10]);
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(super::fingerprint(b""), "cbf29ce484222325");
+        assert_eq!(super::fingerprint(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn migrate_legacy_records() {
+        let mut storage = MockHost::default();
+        storage.persist("a1", &serde_json::to_vec(&1).unwrap());
+
+        assert_eq!(LEGACY_ITEMS.load_outdated::<_, u64>(&storage).len(), 1);
+        assert!(LEGACY_ITEMS.migrate::<_, u64>(&mut storage, "a1").unwrap());
+        assert!(!LEGACY_ITEMS.migrate::<_, u64>(&mut storage, "a1").unwrap());
+        assert!(LEGACY_ITEMS.load_outdated::<_, u64>(&storage).is_empty());
+        assert_eq!(
+            LEGACY_ITEMS.fingerprint(&storage, "a1"),
+            Some(super::fingerprint(br#"{"version":1,"data":1}"#))
+        );
+        assert_eq!(
+            storage.retrieve("legacy_item/a1"),
+            Some(br#"{"version":1,"data":1}"#.to_vec())
+        );
+    }
+}
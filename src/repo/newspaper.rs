@@ -0,0 +1,65 @@
+use crate::{
+    newspaper::{Newspaper, UncheckedNewspaper},
+    Storage,
+};
+
+use super::{Migration, Record, Result, NEWSPAPERS};
+
+impl Record for UncheckedNewspaper {}
+
+/// The catalog of newspapers, stored by signature.
+/// Newspapers are loaded unchecked, since their invariants depend on the current time.
+pub(crate) struct NewspaperRepo;
+
+impl NewspaperRepo {
+    pub(crate) fn contains<S>(storage: &S, signature: &str) -> bool
+    where
+        S: Storage,
+    {
+        NEWSPAPERS.contains(storage, signature)
+    }
+
+    pub(crate) fn find<S>(storage: &S, signature: &str) -> Result<Option<UncheckedNewspaper>>
+    where
+        S: Storage,
+    {
+        NEWSPAPERS.load(storage, signature)
+    }
+
+    pub(crate) fn all<S>(storage: &S) -> Vec<Result<UncheckedNewspaper>>
+    where
+        S: Storage,
+    {
+        NEWSPAPERS.load_all(storage)
+    }
+
+    pub(crate) fn save<S>(storage: &mut S, newspaper: &Newspaper) -> Result<()>
+    where
+        S: Storage,
+    {
+        NEWSPAPERS.save(
+            storage,
+            newspaper.identificator(),
+            &UncheckedNewspaper::from(newspaper),
+        )
+    }
+
+    /// Rewrites the outdated newspapers in the latest version, without checking
+    /// their invariants, so that migrating never loses a record.
+    pub(crate) fn migrate<S>(storage: &mut S) -> Migration
+    where
+        S: Storage,
+    {
+        NEWSPAPERS
+            .load_outdated::<_, UncheckedNewspaper>(storage)
+            .into_iter()
+            .map(|newspaper| {
+                newspaper.and_then(|newspaper| {
+                    NEWSPAPERS
+                        .save(storage, newspaper.signature(), &newspaper)
+                        .map(|()| true)
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    order::{self, Order, OrderNumber},
+    Storage,
+};
+
+use super::{Error, Migration, Record, Result, Upgrade, ORDERS, ORDER_NUMBERS};
+
+impl Record for Order {
+    const UPGRADES: &'static [Upgrade] = &[order::to_line_items];
+}
+
+/// The orders, stored by their internal key, and an index from their public numbers.
+pub(crate) struct OrderRepo;
+
+impl OrderRepo {
+    /// The key of a numbered order, holding no personal data. It starts with the time
+    /// the order was placed, so that the orders placed at the same time are found together.
+    pub(crate) fn key(order: &Order, number: &OrderNumber) -> String {
+        format!("{}_{}", order.created_on_ms(), number.as_str())
+    }
+
+    /// The public number in the key of an order. The legacy keys hold the phone of the
+    /// customer instead, which starts with a digit or '+' unlike the numbers.
+    pub(crate) fn number_in_key(id: &str) -> Option<&str> {
+        id.split_once('_')
+            .map(|(_, rest)| rest)
+            .filter(|rest| rest.starts_with(|c: char| c.is_ascii_alphabetic()))
+    }
+
+    /// The orders placed at the same millisecond, under their current or their legacy keys.
+    pub(crate) fn placed_at<S>(storage: &S, created_on_ms: u64) -> Vec<Result<Order>>
+    where
+        S: Storage,
+    {
+        // '`' follows '_', which separates the time from the rest of the key
+        ORDERS.load_range(
+            storage,
+            &format!("{}_", created_on_ms),
+            &format!("{}`", created_on_ms),
+        )
+    }
+
+    pub(crate) fn contains<S>(storage: &S, id: &str) -> bool
+    where
+        S: Storage,
+    {
+        ORDERS.contains(storage, id)
+    }
+
+    pub(crate) fn all<S>(storage: &S) -> Vec<Result<Order>>
+    where
+        S: Storage,
+    {
+        ORDERS.load_all(storage)
+    }
+
+    /// The id is passed separately, since it cannot be derived from an anonymized order.
+    pub(crate) fn save<S>(storage: &mut S, id: &str, order: &Order) -> Result<()>
+    where
+        S: Storage,
+    {
+        ORDERS.save(storage, id, order)
+    }
+
+    /// Removes the order stored under the key, e.g. after moving it to another key.
+    pub(crate) fn remove<S>(storage: &mut S, id: &str)
+    where
+        S: Storage,
+    {
+        ORDERS.remove(storage, id)
+    }
+
+    pub(crate) fn index_number<S>(storage: &mut S, number: &OrderNumber, id: &str) -> Result<()>
+    where
+        S: Storage,
+    {
+        ORDER_NUMBERS.save(storage, number.as_str(), &id.to_string())
+    }
+
+    pub(crate) fn id_by_number<S>(storage: &S, number: &OrderNumber) -> Result<Option<String>>
+    where
+        S: Storage,
+    {
+        ORDER_NUMBERS.load(storage, number.as_str())
+    }
+
+    /// Rewrites the outdated orders in the latest version, along with their number index.
+    pub(crate) fn migrate<S>(storage: &mut S) -> Migration
+    where
+        S: Storage,
+    {
+        ORDERS
+            .load_outdated::<_, Order>(storage)
+            .into_iter()
+            .map(|order| {
+                order.and_then(|order| {
+                    Self::id(storage, &order).and_then(|id| {
+                        Self::save(storage, &id, &order)
+                            .and_then(|()| match order.number() {
+                                Some(number) => Self::index_number(storage, number, &id),
+                                None => Ok(()),
+                            })
+                            .map(|()| true)
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// The key of the order, which is not stored along with it. A numbered order is found
+    /// through the index, the orders placed before the numbers are keyed by their phone.
+    pub(crate) fn id<S>(storage: &S, order: &Order) -> Result<String>
+    where
+        S: Storage,
+    {
+        match (order.number(), order.is_anonymized()) {
+            (Some(number), _) => {
+                Self::id_by_number(storage, number).and_then(|id| id.ok_or(Error::UnknownOrderKey))
+            }
+            (None, false) => Ok(order.identifier()),
+            (None, true) => Err(Error::UnknownOrderKey),
+        }
+    }
+}
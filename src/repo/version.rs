@@ -0,0 +1,172 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::bindings::ByteArray;
+
+use super::{Error, Result};
+
+/// Converts the JSON of a record from one version to the next one.
+pub(crate) type Upgrade = fn(Value) -> serde_json::Result<Value>;
+
+/// A value persisted in a versioned envelope, e.g. {"version":2,"data":{...}}.
+/// The records written before the envelope was introduced have version 1.
+pub(crate) trait Record: Serialize + DeserializeOwned {
+    /// The upgrade at index `i` converts version `i + 1` to version `i + 2`,
+    /// so the latest version is one more than the number of upgrades.
+    const UPGRADES: &'static [Upgrade] = &[];
+
+    fn version() -> u32 {
+        u32::try_from(Self::UPGRADES.len()).expect("too many upgrades") + 1
+    }
+}
+
+impl Record for u64 {}
+
+impl Record for String {}
+
+#[derive(Serialize)]
+struct Envelope<'a, V> {
+    version: u32,
+    data: &'a V,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoredEnvelope {
+    version: u32,
+    data: Value,
+}
+
+/// A record read from the storage, along with whether it is stored in an older version
+/// or without an envelope.
+pub(super) struct Decoded<V> {
+    pub(super) value: V,
+    pub(super) outdated: bool,
+}
+
+pub(super) fn encode<V>(value: &V) -> Result<ByteArray>
+where
+    V: Record,
+{
+    serde_json::to_vec(&Envelope {
+        version: V::version(),
+        data: value,
+    })
+    .map_err(Error::SerializationFault)
+}
+
+pub(super) fn decode<V>(ser: &[u8]) -> Result<Decoded<V>>
+where
+    V: Record,
+{
+    let json: Value = serde_json::from_slice(ser).map_err(Error::DeserializationFault)?;
+    let enveloped = is_envelope(&json);
+    let (version, data) = if enveloped {
+        serde_json::from_value::<StoredEnvelope>(json)
+            .map(|envelope| (envelope.version, envelope.data))
+            .map_err(Error::DeserializationFault)?
+    } else {
+        (1, json)
+    };
+
+    upgrade::<V>(version, data).and_then(|data| {
+        serde_json::from_value(data)
+            .map_err(Error::DeserializationFault)
+            .map(|value| Decoded {
+                value,
+                outdated: !enveloped || version < V::version(),
+            })
+    })
+}
+
+fn is_envelope(json: &Value) -> bool {
+    json.as_object().is_some_and(|fields| {
+        fields.len() == 2 && fields.contains_key("version") && fields.contains_key("data")
+    })
+}
+
+fn upgrade<V>(version: u32, data: Value) -> Result<Value>
+where
+    V: Record,
+{
+    if version == 0 || version > V::version() {
+        return Err(Error::UnknownVersion(version));
+    }
+    usize::try_from(version - 1)
+        .map_err(|_| Error::UnknownVersion(version))
+        .and_then(|applied| {
+            V::UPGRADES
+                .iter()
+                .zip(1..)
+                .skip(applied)
+                .try_fold(data, |data, (upgrade, from)| {
+                    upgrade(data).map_err(|error| Error::UpgradeFault(from, error))
+                })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use super::{Record, Upgrade};
+
+    /// Version 1 had only a name, version 2 added the age.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    impl Record for Person {
+        const UPGRADES: &'static [Upgrade] = &[add_age];
+    }
+
+    fn add_age(mut value: Value) -> serde_json::Result<Value> {
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("age".to_string(), Value::from(0));
+        }
+        Ok(value)
+    }
+
+    #[test]
+    fn latest_version() {
+        let person = Person {
+            name: "Иван".to_string(),
+            age: 30,
+        };
+        let ser = super::encode(&person).unwrap();
+        assert_eq!(
+            String::from_utf8(ser.clone()).unwrap(),
+            r#"{"version":2,"data":{"name":"Иван","age":30}}"#
+        );
+
+        let decoded = super::decode::<Person>(&ser).unwrap();
+        assert_eq!(decoded.value, person);
+        assert!(!decoded.outdated);
+    }
+
+    #[test]
+    fn upgrade_unversioned() {
+        let decoded = super::decode::<Person>(r#"{"name":"Иван"}"#.as_bytes()).unwrap();
+        assert_eq!(decoded.value.age, 0);
+        assert!(decoded.outdated);
+
+        let decoded =
+            super::decode::<Person>(r#"{"version":1,"data":{"name":"Иван"}}"#.as_bytes()).unwrap();
+        assert_eq!(decoded.value.age, 0);
+        assert!(decoded.outdated);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let res =
+            super::decode::<Person>(r#"{"version":3,"data":{"name":"Иван","age":30}}"#.as_bytes());
+        assert!(res
+            .err()
+            .expect("expected an error")
+            .to_string()
+            .contains("version 3"));
+    }
+}
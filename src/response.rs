@@ -1,33 +1,254 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 
-use crate::{bindings::ByteArray, services::ServiceError};
+use crate::{
+    bindings::{self, component::dnevest::time::Clock, ByteArray},
+    newspaper::{DateFormat, Newspaper, Year},
+    order::{DeliverySchedule, OrderSummary, PriceList},
+    services::{ImportedRow, ServiceError},
+};
 
-#[derive(Serialize, Debug, Deserialize)]
+/// The version of the event schema, increased on every incompatible change of the payloads.
+/// Version 1 carried only the id of the changed record, without an envelope.
+const EVENT_VERSION: u32 = 2;
+
+/// The ids of the events, under which the host publishes them.
+pub(crate) mod id {
+    pub(crate) const NEWSPAPER_CREATED: &str = "dnevest_n_n";
+    pub(crate) const END_YEAR_ADDED: &str = "dnevest_end_y";
+    pub(crate) const MAX_CARDS_SPECIFIED: &str = "dnevest_max_card";
+    pub(crate) const DELIVERY_SCHEDULE_SPECIFIED: &str = "dnevest_dlv_sched";
+    pub(crate) const ORDER_SAVED: &str = "dnevest_n_o";
+    pub(crate) const RETENTION_SPECIFIED: &str = "dnevest_retention";
+    pub(crate) const CUSTOMER_DATA_ERASED: &str = "dnevest_erase";
+    pub(crate) const ORDERS_ANONYMIZED: &str = "dnevest_anon";
+    pub(crate) const RECORDS_MIGRATED: &str = "dnevest_migrate";
+    pub(crate) const NEWSPAPERS_IMPORTED: &str = "dnevest_import";
+    pub(crate) const SNAPSHOT_RESTORED: &str = "dnevest_restore";
+    pub(crate) const DATE_FORMAT_SPECIFIED: &str = "dnevest_date_fmt";
+    pub(crate) const PRICE_LIST_SPECIFIED: &str = "dnevest_prices";
+}
+
+/// Each event carries everything the consumers need, so that they do not have to query back.
+/// The personal data of the customers is left out.
+#[derive(Serialize, JsonSchema)]
 pub enum Event {
-    NewspaperCreated(String),
-    AddedEndYear(String),
-    SpecifiedMaxCards(String),
-    SavedOrder(String),
+    NewspaperCreated {
+        newspaper: Newspaper,
+    },
+    AddedEndYear {
+        signature: String,
+        old_end_year: Option<Year>,
+        new_end_year: Year,
+    },
+    SpecifiedMaxCards {
+        max_number: u8,
+    },
+    SpecifiedDeliverySchedule {
+        schedule: DeliverySchedule,
+    },
+    SavedOrder(OrderSummary),
+    SpecifiedRetention {
+        days: u16,
+    },
+    CustomerDataErased {
+        order_numbers: Vec<String>,
+        /// The orders that could not be read, so they could not be checked either.
+        unreadable: u32,
+    },
+    OrdersAnonymized {
+        order_numbers: Vec<String>,
+        /// The orders that could not be read, so they could not be checked either.
+        unreadable: u32,
+    },
+    RecordsMigrated {
+        migrated: u32,
+        failed: u32,
+    },
+    NewspapersImported {
+        rows: Vec<ImportedRow>,
+    },
+    SnapshotRestored {
+        restored: u32,
+    },
+    SpecifiedDateFormat {
+        format: DateFormat,
+    },
+    SpecifiedPriceList {
+        prices: PriceList,
+    },
+}
+
+/// What the consumers receive, e.g. {"version":2,"timestamp":1732880395000,"event":{...}}.
+/// The events of a message sent in a deprecated version carry its `deprecation` warning.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(bound(deserialize = "&'a E: Deserialize<'de>"))]
+pub(crate) struct Published<'a, E: ?Sized = Event> {
+    version: u32,
+    timestamp: u64,
+    #[serde(borrow)]
+    event: &'a E,
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    deprecation: Option<&'a str>,
+}
+
+impl Published<'_, RawValue> {
+    /// Adds the warning to an event as it was published.
+    pub(crate) fn deprecate(content: &[u8], deprecation: &str) -> serde_json::Result<ByteArray> {
+        serde_json::from_slice::<Published<'_, RawValue>>(content).and_then(|published| {
+            serde_json::to_vec(&Published {
+                deprecation: Some(deprecation),
+                ..published
+            })
+        })
+    }
 }
 
 impl Event {
-    pub(crate) fn newspaper_created(signature: &str) -> Self {
-        Event::NewspaperCreated(signature.to_string())
+    pub(crate) fn newspaper_created(newspaper: Newspaper) -> Self {
+        Event::NewspaperCreated { newspaper }
+    }
+
+    pub(crate) fn added_end_year(
+        signature: &str,
+        old_end_year: Option<Year>,
+        new_end_year: Year,
+    ) -> Self {
+        Event::AddedEndYear {
+            signature: signature.to_string(),
+            old_end_year,
+            new_end_year,
+        }
+    }
+
+    pub(crate) fn specified_max_cards(max_number: u8) -> Self {
+        Event::SpecifiedMaxCards { max_number }
+    }
+
+    pub(crate) fn specified_delivery_schedule(schedule: DeliverySchedule) -> Self {
+        Event::SpecifiedDeliverySchedule { schedule }
+    }
+
+    pub(crate) fn saved_order(summary: OrderSummary) -> Self {
+        Event::SavedOrder(summary)
+    }
+
+    pub(crate) fn specified_retention(days: u16) -> Self {
+        Event::SpecifiedRetention { days }
     }
 
-    pub(crate) fn added_end_year(signature: &str) -> Self {
-        Event::AddedEndYear(signature.to_string())
+    pub(crate) fn customer_data_erased(order_numbers: Vec<String>, unreadable: u32) -> Self {
+        Event::CustomerDataErased {
+            order_numbers,
+            unreadable,
+        }
     }
 
-    pub(crate) fn specified_max_cards(id: &str) -> Self {
-        Event::SpecifiedMaxCards(id.to_string())
+    pub(crate) fn orders_anonymized(order_numbers: Vec<String>, unreadable: u32) -> Self {
+        Event::OrdersAnonymized {
+            order_numbers,
+            unreadable,
+        }
     }
 
-    pub(crate) fn saved_order(id: &str) -> Self {
-        Event::SavedOrder(id.to_string())
+    pub(crate) fn records_migrated(migrated: u32, failed: u32) -> Self {
+        Event::RecordsMigrated { migrated, failed }
     }
 
-    pub(crate) fn serialize(&self) -> Result<ByteArray, ServiceError> {
-        serde_json::to_vec(&self).map_err(ServiceError::SerializationFault)
+    pub(crate) fn newspapers_imported(rows: Vec<ImportedRow>) -> Self {
+        Event::NewspapersImported { rows }
+    }
+
+    pub(crate) fn snapshot_restored(restored: u32) -> Self {
+        Event::SnapshotRestored { restored }
+    }
+
+    pub(crate) fn specified_date_format(format: DateFormat) -> Self {
+        Event::SpecifiedDateFormat { format }
+    }
+
+    pub(crate) fn specified_price_list(prices: PriceList) -> Self {
+        Event::SpecifiedPriceList { prices }
+    }
+
+    pub(crate) fn id(&self) -> &'static str {
+        match self {
+            Event::NewspaperCreated { .. } => id::NEWSPAPER_CREATED,
+            Event::AddedEndYear { .. } => id::END_YEAR_ADDED,
+            Event::SpecifiedMaxCards { .. } => id::MAX_CARDS_SPECIFIED,
+            Event::SpecifiedDeliverySchedule { .. } => id::DELIVERY_SCHEDULE_SPECIFIED,
+            Event::SavedOrder(_) => id::ORDER_SAVED,
+            Event::SpecifiedRetention { .. } => id::RETENTION_SPECIFIED,
+            Event::CustomerDataErased { .. } => id::CUSTOMER_DATA_ERASED,
+            Event::OrdersAnonymized { .. } => id::ORDERS_ANONYMIZED,
+            Event::RecordsMigrated { .. } => id::RECORDS_MIGRATED,
+            Event::NewspapersImported { .. } => id::NEWSPAPERS_IMPORTED,
+            Event::SnapshotRestored { .. } => id::SNAPSHOT_RESTORED,
+            Event::SpecifiedDateFormat { .. } => id::DATE_FORMAT_SPECIFIED,
+            Event::SpecifiedPriceList { .. } => id::PRICE_LIST_SPECIFIED,
+        }
+    }
+
+    /// Writes the dates of the event, if any, in the format.
+    pub(crate) fn serialize(
+        &self,
+        now: Clock,
+        format: DateFormat,
+    ) -> Result<ByteArray, ServiceError> {
+        format
+            .to_vec(&Published {
+                version: EVENT_VERSION,
+                timestamp: now.timestamp,
+                event: self,
+                deprecation: None,
+            })
+            .map_err(ServiceError::SerializationFault)
+    }
+
+    pub(crate) fn publish(
+        &self,
+        now: Clock,
+        format: DateFormat,
+    ) -> Result<bindings::Event, ServiceError> {
+        self.serialize(now, format).map(|content| bindings::Event {
+            id: self.id().to_string(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        bindings::component::dnevest::time::Clock,
+        newspaper::{DateFormat, Newspaper},
+    };
+
+    use super::Event;
+
+    #[test]
+    fn published_event() {
+        let newspaper = Newspaper::new_unchecked(
+            "В4667",
+            "Орбита",
+            1969,
+            Some(1991),
+            [false, false, false, false, false, true, false],
+        );
+        let event = Event::newspaper_created(newspaper)
+            .publish(
+                Clock {
+                    timestamp: 1732880395000,
+                },
+                DateFormat::default(),
+            )
+            .unwrap();
+
+        assert_eq!(event.id, "dnevest_n_n");
+        assert_eq!(
+            String::from_utf8(event.content).unwrap(),
+            r#"{"version":2,"timestamp":1732880395000,"event":{"NewspaperCreated":{"newspaper":{"signature":"В4667","name":"Орбита","start_year":1969,"end_year":1991,"weekly_schedule":[false,false,false,false,false,true,false]}}}}"#
+        );
     }
 }
@@ -0,0 +1,158 @@
+//! Runs the scenarios in the `scenarios` directory, each a sequence of messages along with
+//! the events, responses or errors they are expected to return at a fixed time.
+//! The messages are run as the host runs them, so that a change of their JSON fails a scenario.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use std::{cell::RefCell, fs, path::Path};
+
+use crate::{
+    bindings::ByteArray,
+    services::{MockClock, MockHost},
+    Storage,
+};
+
+const SCENARIOS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+
+thread_local! {
+    static STORE: RefCell<MockHost> = RefCell::new(MockHost::empty());
+}
+
+/// Every storage created while running a scenario shares the same records, as on the host.
+#[derive(Default)]
+struct SharedHost;
+
+impl Storage for SharedHost {
+    fn persist(&mut self, key: &str, value: &ByteArray) {
+        STORE.with_borrow_mut(|store| store.persist(key, value))
+    }
+
+    fn retrieve(&self, key: &str) -> Option<ByteArray> {
+        STORE.with_borrow(|store| store.retrieve(key))
+    }
+
+    fn retrieve_range(&self, start: &str, end: &str) -> Vec<ByteArray> {
+        STORE.with_borrow(|store| store.retrieve_range(start, end))
+    }
+}
+
+/// The scenario starts with an empty storage.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    #[allow(dead_code)]
+    description: String,
+    now_ms: u64,
+    steps: Vec<Step>,
+}
+
+/// Either an execution or a query. If no outcome is expected, the message only has to succeed.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Step {
+    /// Moves the clock before the message.
+    #[serde(default)]
+    now_ms: Option<u64>,
+    #[serde(default)]
+    execute: Option<Value>,
+    #[serde(default)]
+    query: Option<Value>,
+    /// The id of each event along with its content.
+    #[serde(default)]
+    events: Option<Value>,
+    #[serde(default)]
+    response: Option<Value>,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+impl Step {
+    /// Runs the message at the time of the clock and tells how its outcome differs from
+    /// the expected one, if it does.
+    fn run(self, clock: &MockClock) -> Option<String> {
+        let (outcome, expected) = match (self.execute, self.query) {
+            (Some(msg), None) => (
+                crate::execute::<SharedHost, MockClock>(bytes(&msg), clock).map(|events| {
+                    Value::from_iter(
+                        events
+                            .into_iter()
+                            .map(|event| json!({"id": event.id, "content": parse(&event.content)})),
+                    )
+                }),
+                self.events,
+            ),
+            (None, Some(msg)) => (
+                crate::query::<SharedHost, MockClock>(bytes(&msg), clock)
+                    .map(|response| parse(&response)),
+                self.response,
+            ),
+            _ => return Some("expected either an execution or a query".to_string()),
+        };
+        match (outcome.map_err(|error| parse(&error)), self.error) {
+            (Ok(actual), None) if expected.as_ref().is_none_or(|expected| *expected == actual) => {
+                None
+            }
+            (Ok(actual), None) => Some(format!("returned {}", actual)),
+            (Err(actual), Some(expected)) if actual == expected => None,
+            (Err(actual), _) => Some(format!("failed with {}", actual)),
+            (Ok(actual), Some(_)) => Some(format!("succeeded with {}", actual)),
+        }
+    }
+}
+
+fn bytes(msg: &Value) -> ByteArray {
+    serde_json::to_vec(msg).expect("a JSON value is serializable")
+}
+
+/// The events, the responses and the errors are JSON, except for the CSV exports.
+fn parse(content: &[u8]) -> Value {
+    serde_json::from_slice(content)
+        .unwrap_or_else(|_| Value::from(String::from_utf8_lossy(content).into_owned()))
+}
+
+/// Runs the scenario on a fresh storage, stops at the first unexpected outcome.
+fn run(path: &Path) -> Result<(), String> {
+    let scenario: Scenario = fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|error| error.to_string()))?;
+    STORE.set(MockHost::empty());
+    let mut clock = MockClock::at(scenario.now_ms);
+    scenario
+        .steps
+        .into_iter()
+        .enumerate()
+        .find_map(|(index, step)| {
+            // the clock stays where the last step moved it
+            if let Some(now_ms) = step.now_ms {
+                clock = MockClock::at(now_ms);
+            }
+            step.run(&clock)
+                .map(|error| format!("step {}: {}", index, error))
+        })
+        .map_or(Ok(()), Err)
+}
+
+#[test]
+fn scenarios() {
+    let mut paths: Vec<_> = fs::read_dir(SCENARIOS)
+        .expect("the scenarios directory exists")
+        .map(|entry| entry.expect("the scenario can be listed").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let failures: Vec<String> = paths
+        .iter()
+        .filter_map(|path| {
+            run(path)
+                .err()
+                .map(|error| format!("{}: {}", path.display(), error))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,250 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::result::Result as StdResult;
+
+use crate::{
+    order::OrderNumber,
+    repo::{self, CounterRepo, Namespace, Record, AUDIT, ORDERS},
+    Storage, Time,
+};
+
+use super::ServiceError;
+
+/// How many entries a single query returns at most.
+const MAX_LIMIT: u32 = 100;
+
+/// The messages that change the state, as named in the requests.
+pub(super) mod message {
+    pub(crate) const CREATE_NEWSPAPER: &str = "CreateNewspaper";
+    pub(crate) const ADD_FINAL_YEAR: &str = "AddFinalYear";
+    pub(crate) const SPECIFY_MAX_CARDS: &str = "SpecifyMaxCards";
+    pub(crate) const SPECIFY_DELIVERY_SCHEDULE: &str = "SpecifyDeliverySchedule";
+    pub(crate) const CREATE_ORDER: &str = "CreateOrder";
+    pub(crate) const SPECIFY_RETENTION: &str = "SpecifyRetention";
+    pub(crate) const ERASE_CUSTOMER_DATA: &str = "EraseCustomerData";
+    pub(crate) const ENFORCE_RETENTION: &str = "EnforceRetention";
+    pub(crate) const IMPORT_NEWSPAPERS: &str = "ImportNewspapers";
+    pub(crate) const RESTORE: &str = "Restore";
+    pub(crate) const SPECIFY_DATE_FORMAT: &str = "SpecifyDateFormat";
+    pub(crate) const SPECIFY_PRICE_LIST: &str = "SpecifyPriceList";
+}
+
+/// A record about to be changed by a message, along with its fingerprint before the change.
+pub(super) struct Change<'a> {
+    message: &'static str,
+    namespace: &'a Namespace,
+    id: String,
+    /// How the record appears in the log, usually its storage key.
+    record: String,
+    before: Option<String>,
+}
+
+impl<'a> Change<'a> {
+    pub(super) fn new<S>(
+        storage: &S,
+        message: &'static str,
+        namespace: &'a Namespace,
+        id: &str,
+    ) -> Self
+    where
+        S: Storage,
+    {
+        Self {
+            message,
+            namespace,
+            id: id.to_string(),
+            record: namespace.key(id),
+            before: namespace.fingerprint(storage, id),
+        }
+    }
+
+    /// The orders placed before they were numbered are keyed by the phone of the customer,
+    /// so an order appears in the log by its public number instead.
+    pub(super) fn order<S>(
+        storage: &S,
+        message: &'static str,
+        key: &str,
+        number: Option<&OrderNumber>,
+    ) -> Self
+    where
+        S: Storage,
+    {
+        Self {
+            record: number.map_or_else(
+                || ORDERS.name().to_string(),
+                |number| ORDERS.key(number.as_str()),
+            ),
+            ..Self::new(storage, message, &ORDERS, key)
+        }
+    }
+
+    /// The record is moved to another id by the change, e.g. an order away from its phone.
+    pub(super) fn moved_to(self, id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            ..self
+        }
+    }
+}
+
+/// An entry of the append-only audit log. The contents of the changed record are identified
+/// by a fingerprint before and after the change, the record is missing before its creation.
+/// Migrations only rewrite the records in their latest version, so they are not logged.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(super) struct AuditEntry {
+    seq: u64,
+    message: String,
+    /// Who sent the message, once the requests are authenticated.
+    actor: Option<String>,
+    timestamp: u64,
+    record: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Record for AuditEntry {}
+
+impl AuditEntry {
+    /// The entry is stored under its sequence number.
+    pub(super) fn id(&self) -> String {
+        seq_id(self.seq)
+    }
+}
+
+/// Appends an entry about the persisted change to the audit log.
+pub(super) fn record<S, T>(
+    storage: &mut S,
+    clock: &T,
+    change: Change<'_>,
+) -> StdResult<(), ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    next_seq(storage)
+        .and_then(|seq| {
+            let entry = AuditEntry {
+                seq,
+                message: change.message.to_string(),
+                actor: None,
+                timestamp: clock.now().timestamp,
+                after: change.namespace.fingerprint(storage, &change.id),
+                record: change.record,
+                before: change.before,
+            };
+            AUDIT.save(storage, &seq_id(seq), &entry)
+        })
+        .map_err(ServiceError::Storage)
+}
+
+/// The next free sequence number. A restored log may hold entries beyond the counter,
+/// e.g. until its counter is restored as well, so their sequence numbers are skipped.
+fn next_seq<S>(storage: &mut S) -> repo::Result<u64>
+where
+    S: Storage,
+{
+    loop {
+        let seq = CounterRepo::next(storage, CounterRepo::AUDIT)?;
+        if !AUDIT.contains(storage, &seq_id(seq)) {
+            return Ok(seq);
+        }
+    }
+}
+
+/// The entries starting from the given sequence number, in order.
+pub(super) fn log<S>(
+    storage: &S,
+    from_seq: u64,
+    limit: u32,
+) -> StdResult<Vec<AuditEntry>, ServiceError>
+where
+    S: Storage,
+{
+    let to_seq = from_seq.saturating_add(u64::from(limit.min(MAX_LIMIT)));
+    AUDIT
+        .load_range(storage, &seq_id(from_seq), &seq_id(to_seq))
+        .into_iter()
+        .collect::<repo::Result<Vec<AuditEntry>>>()
+        .map(|mut entries| {
+            // the order of the range depends on the host
+            entries.sort_by_key(|entry| entry.seq);
+            entries
+        })
+        .map_err(ServiceError::Storage)
+}
+
+/// Padded, so that the keys are sorted by the sequence number.
+fn seq_id(seq: u64) -> String {
+    format!("{:020}", seq)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        order::MaxCards,
+        repo::{ConfigRepo, CounterRepo, CONFIG, COUNTERS},
+        services::{MockClock, MockHost},
+    };
+
+    use super::{message, Change};
+
+    #[test]
+    fn before_and_after() {
+        let mut storage = MockHost::default();
+        let before = CONFIG.fingerprint(&storage, "max_cards");
+        specify_max_cards(&mut storage, 30);
+        specify_max_cards(&mut storage, 20);
+
+        let log = super::log(&storage, 1, 10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].seq, 1);
+        assert_eq!(log[0].record, "config/max_cards");
+        assert_eq!(log[0].message, "SpecifyMaxCards");
+        assert_eq!(log[0].timestamp, 1732880395000);
+        assert_eq!(log[0].before, before);
+        assert_eq!(log[1].before, log[0].after);
+        assert_ne!(log[1].before, log[1].after);
+        assert_eq!(log[1].after, CONFIG.fingerprint(&storage, "max_cards"));
+    }
+
+    #[test]
+    fn paging() {
+        let mut storage = MockHost::default();
+        (30..35).for_each(|max| specify_max_cards(&mut storage, max));
+
+        let seqs = |from, limit| -> Vec<u64> {
+            super::log(&storage, from, limit)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.seq)
+                .collect()
+        };
+        assert_eq!(seqs(1, 2), vec![1, 2]);
+        assert_eq!(seqs(3, 10), vec![3, 4, 5]);
+        assert!(seqs(6, 10).is_empty());
+        assert!(seqs(1, 0).is_empty());
+    }
+
+    #[test]
+    fn taken_seq() {
+        let mut storage = MockHost::default();
+        specify_max_cards(&mut storage, 30);
+        // e.g. the log is restored before its counter
+        COUNTERS
+            .save(&mut storage, CounterRepo::AUDIT, &0u64)
+            .unwrap();
+        specify_max_cards(&mut storage, 20);
+
+        let log = super::log(&storage, 1, 10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[1].seq, 2);
+    }
+
+    fn specify_max_cards(storage: &mut MockHost, max: u8) {
+        let change = Change::new(storage, message::SPECIFY_MAX_CARDS, &CONFIG, "max_cards");
+        ConfigRepo::save(storage, &MaxCards::new(max)).unwrap();
+        super::record::<_, MockClock>(storage, &MockClock::default(), change).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use serde::Serialize;
+
+use std::{collections::BTreeMap, ops::Bound, result::Result as StdResult};
+
+use crate::{
+    bindings::{self, ByteArray},
+    msgs::ExecuteMsg,
+    Storage, Time,
+};
+
+use super::ServiceError;
+
+/// Why a message of a rolled back batch failed.
+#[derive(Serialize, Debug)]
+pub(crate) struct Failure {
+    index: usize,
+    error: String,
+}
+
+/// Stages the writes in memory and reads them back over the underlying storage,
+/// so that nothing is persisted until the whole batch succeeds.
+struct Overlay<'a, S> {
+    storage: &'a mut S,
+    staged: BTreeMap<String, ByteArray>,
+}
+
+impl<'a, S> Overlay<'a, S>
+where
+    S: Storage,
+{
+    fn new(storage: &'a mut S) -> Self {
+        Self {
+            storage,
+            staged: BTreeMap::new(),
+        }
+    }
+
+    fn commit(self) {
+        self.staged
+            .iter()
+            .for_each(|(key, value)| self.storage.persist(key, value));
+    }
+}
+
+impl<S> Storage for Overlay<'_, S>
+where
+    S: Storage,
+{
+    fn persist(&mut self, key: &str, value: &ByteArray) {
+        self.staged.insert(key.to_string(), value.clone());
+    }
+
+    fn retrieve(&self, key: &str) -> Option<ByteArray> {
+        self.staged
+            .get(key)
+            .cloned()
+            .or_else(|| self.storage.retrieve(key))
+    }
+
+    /// The range of the underlying storage holds only the values, so it is read in the pieces
+    /// between the staged keys, each staged value taking the place of the underlying one.
+    fn retrieve_range(&self, start: &str, end: &str) -> Vec<ByteArray> {
+        if start >= end {
+            return Vec::new();
+        }
+        let mut values = Vec::new();
+        let mut from = start.to_string();
+        self.staged
+            .range::<str, _>((Bound::Included(start), Bound::Excluded(end)))
+            .for_each(|(key, value)| {
+                values.extend(self.storage.retrieve_range(&from, key));
+                values.push(value.clone());
+                // the first key after the staged one
+                from = format!("{}\0", key);
+            });
+        values.extend(self.storage.retrieve_range(&from, end));
+        values
+    }
+}
+
+/// Executes the messages in order, each one seeing the changes of the previous ones,
+/// and persists the changes only if all of them succeed.
+pub(super) fn run<S, H, T>(
+    storage: &mut S,
+    clock: &T,
+    msgs: Vec<ExecuteMsg<H>>,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    H: Storage + Default,
+    T: Time,
+{
+    let mut batch = Overlay::new(storage);
+    let mut events = Vec::new();
+    let mut failures = Vec::new();
+    msgs.into_iter().enumerate().for_each(|(index, msg)| {
+        // a failed message leaves no changes behind for the next ones
+        let mut overlay = Overlay::new(&mut batch);
+        match super::apply::<_, H, T>(&mut overlay, clock, msg) {
+            Ok(applied) => {
+                overlay.commit();
+                events.extend(applied);
+            }
+            Err(error) => failures.push(Failure {
+                index,
+                error: error.to_string(),
+            }),
+        }
+    });
+
+    if failures.is_empty() {
+        batch.commit();
+        Ok(events)
+    } else {
+        Err(ServiceError::Batch(failures))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        msgs::ExecuteMsg,
+        newspaper::Signature,
+        repo::{NewspaperRepo, OrderRepo},
+        services::{MockClock, MockHost, ServiceError},
+        Storage,
+    };
+
+    use super::Overlay;
+
+    #[test]
+    fn all_succeed() {
+        let mut storage = MockHost::default();
+        let msgs = vec![create_newspaper("В1905"), add_final_year("В1905", 1996)];
+
+        let events =
+            super::run::<_, MockHost, MockClock>(&mut storage, &MockClock::default(), msgs)
+                .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].id, "dnevest_end_y");
+        assert!(NewspaperRepo::contains(&storage, "В1905"));
+    }
+
+    #[test]
+    fn nothing_persisted_on_failure() {
+        let mut storage = MockHost::default();
+        let msgs = vec![
+            create_newspaper("В1905"),
+            create_newspaper("В1612"),
+            create_newspaper("В1907"),
+            add_final_year("В1907", 1990),
+            add_final_year("В1906", 1990),
+        ];
+
+        let error = super::run::<_, MockHost, MockClock>(&mut storage, &MockClock::default(), msgs)
+            .expect_err("expected the batch to fail");
+        assert!(String::from_utf8(error.serialize())
+            .unwrap()
+            .starts_with(r#"[{"index":1,"error":"Cannot create the newspaper"#));
+        let failures = match error {
+            ServiceError::Batch(failures) => failures,
+            _ => panic!("expected the batch to fail"),
+        };
+        let indexes: Vec<usize> = failures.iter().map(|failure| failure.index).collect();
+        assert_eq!(indexes, vec![1, 4]);
+        assert!(failures[0].error.contains("signature already exists"));
+        assert!(failures[1].error.contains("Newspaper not found"));
+        ["В1905", "В1907"]
+            .iter()
+            .for_each(|signature| assert!(!NewspaperRepo::contains(&storage, signature)));
+    }
+
+    #[test]
+    fn order_sees_previous_messages() {
+        let mut storage = MockHost::default();
+        let msgs = vec![
+            create_newspaper("В1905"),
+            ExecuteMsg::SpecifyMaxCards { max_number: 50 },
+            create_order("В1905", 45),
+        ];
+
+        let events =
+            super::run::<_, MockHost, MockClock>(&mut storage, &MockClock::default(), msgs)
+                .unwrap();
+        assert_eq!(events[2].id, "dnevest_n_o");
+        assert_eq!(OrderRepo::all(&storage).len(), 1);
+    }
+
+    #[test]
+    fn nested_batch() {
+        let msgs = vec![ExecuteMsg::Batch(vec![create_newspaper("В1905")])];
+        let res = super::run::<_, MockHost, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            msgs,
+        );
+        match res {
+            Err(ServiceError::Batch(failures)) => {
+                assert!(failures[0].error.contains("another batch"))
+            }
+            _ => panic!("expected the batch to fail"),
+        }
+    }
+
+    #[test]
+    fn overlay_range() {
+        let mut storage = MockHost::default();
+        storage.persist("item/1", &b"a".to_vec());
+        storage.persist("item/2", &b"b".to_vec());
+        storage.persist("item/4", &b"d".to_vec());
+
+        let mut overlay = Overlay::new(&mut storage);
+        overlay.persist("item/2", &b"c".to_vec());
+        overlay.persist("item/3", &b"d".to_vec());
+        overlay.persist("other/1", &b"e".to_vec());
+        assert_eq!(overlay.retrieve("item/2"), Some(b"c".to_vec()));
+
+        // merged by key, so the same values of different keys are kept
+        let range = overlay.retrieve_range("item/", "item0");
+        assert_eq!(
+            range,
+            vec![b"a".to_vec(), b"c".to_vec(), b"d".to_vec(), b"d".to_vec()]
+        );
+        assert_eq!(
+            overlay.retrieve_range("item/2", "item/3"),
+            vec![b"c".to_vec()]
+        );
+
+        overlay.commit();
+        assert_eq!(storage.retrieve("item/2"), Some(b"c".to_vec()));
+        assert_eq!(storage.retrieve("item/3"), Some(b"d".to_vec()));
+    }
+
+    fn create_newspaper(signature: &str) -> ExecuteMsg<MockHost> {
+        let input = format!(
+            r#"{{"signature":"{}","name":"Поглед","start_year":1966,"end_year":null,"weekly_schedule":[true,false,false,false,false,false,false]}}"#,
+            signature
+        );
+        ExecuteMsg::CreateNewspaper {
+            input: serde_json::from_str(&input).unwrap(),
+        }
+    }
+
+    fn create_order(signature: &str, template_id: u8) -> ExecuteMsg<MockHost> {
+        let order = format!(
+            r#"{{"wish_card":{{"covers":{{"preference":"{}","options":[null,null]}},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Arial","font_size":12,"template_id":{}}},"delivery":{{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}}}"#,
+            signature, template_id
+        );
+        ExecuteMsg::CreateOrder {
+            order: serde_json::from_str(&order).unwrap(),
+            idempotency_key: None,
+        }
+    }
+
+    fn add_final_year(signature: &str, final_year: u16) -> ExecuteMsg<MockHost> {
+        ExecuteMsg::AddFinalYear {
+            signature: Signature::new(signature),
+            final_year,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::result::Result as StdResult;
+
+use crate::{
+    newspaper::{csv, Signature, UncheckedNewspaper, AFTER_SIGN, SIGN},
+    repo::NEWSPAPERS,
+    Storage,
+};
+
+use super::ServiceError;
+
+/// How many newspapers a single page holds at most.
+const PAGE_SIZE: usize = 100;
+/// The signatures are numbered from 1 up to here, exclusive.
+const SIGNATURES: u32 = 10_000;
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Clone, Copy, JsonSchema)]
+pub(crate) enum CatalogFormat {
+    /// The layout of the import, with a header on the first page.
+    Csv,
+    /// A newspaper in JSON on every line.
+    JsonLines,
+}
+
+/// A page of the catalog, ordered by signature. The next page starts from `next_cursor`,
+/// which is missing on the last page. `skipped` counts the records of the page that cannot
+/// be read, so they are left out.
+#[derive(Serialize, JsonSchema)]
+pub(super) struct CatalogPage {
+    content: String,
+    skipped: u32,
+    next_cursor: Option<String>,
+}
+
+/// Exports the newspapers as they are stored, including the ones violating their invariants,
+/// so that the export can serve as a backup. The signatures are read in windows growing from
+/// the cursor until the page is full, so a page reads about as many records as it holds.
+pub(super) fn export<S>(
+    storage: &S,
+    format: CatalogFormat,
+    cursor: Option<&Signature>,
+) -> StdResult<CatalogPage, ServiceError>
+where
+    S: Storage,
+{
+    let mut from = cursor.map_or(1, |cursor| number(cursor.as_str()));
+    let mut window = 2 * PAGE_SIZE as u32;
+    let mut newspapers = Vec::new();
+    let mut skipped = 0;
+    let mut last_window = (from, 0);
+    while newspapers.len() <= PAGE_SIZE && from < SIGNATURES {
+        let to = (from + window).min(SIGNATURES);
+        let (read, unreadable) = self::read(storage, &key(from), &key(to));
+        newspapers.extend(read);
+        skipped += unreadable;
+        last_window = (from, unreadable);
+        from = to;
+        window *= 2;
+    }
+    let next_cursor = newspapers
+        .get(PAGE_SIZE)
+        .map(|newspaper| newspaper.signature().to_string());
+    newspapers.truncate(PAGE_SIZE);
+    // the unreadable records of the last window after the next cursor belong to the next page
+    if let Some(next_cursor) = &next_cursor {
+        let (start, unreadable) = last_window;
+        skipped = skipped - unreadable + self::read(storage, &key(start), next_cursor).1;
+    }
+
+    let lines = match format {
+        CatalogFormat::Csv => cursor
+            .is_none()
+            .then(csv::header)
+            .into_iter()
+            .chain(newspapers.iter().map(csv::row))
+            .collect(),
+        CatalogFormat::JsonLines => newspapers
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<serde_json::Result<Vec<String>>>()
+            .map_err(ServiceError::SerializationFault)?,
+    };
+    Ok(CatalogPage {
+        content: lines.into_iter().map(|line| line + "\n").collect(),
+        skipped,
+        next_cursor,
+    })
+}
+
+/// The newspapers with signatures from `start` to `end`, exclusive, ordered by signature,
+/// along with the number of the records that cannot be read.
+fn read<S>(storage: &S, start: &str, end: &str) -> (Vec<UncheckedNewspaper>, u32)
+where
+    S: Storage,
+{
+    let (mut newspapers, unreadable) = NEWSPAPERS
+        .load_range::<_, UncheckedNewspaper>(storage, start, end)
+        .into_iter()
+        .fold(
+            (Vec::new(), 0),
+            |(mut newspapers, unreadable), newspaper| match newspaper {
+                Ok(newspaper) => {
+                    newspapers.push(newspaper);
+                    (newspapers, unreadable)
+                }
+                Err(_) => (newspapers, unreadable + 1),
+            },
+        );
+    newspapers.sort_by(|a, b| a.signature().cmp(b.signature()));
+    (newspapers, unreadable)
+}
+
+/// The number of the signature, e.g. 1645 of В1645.
+fn number(signature: &str) -> u32 {
+    signature
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .fold(0, |number, digit| number * 10 + digit)
+}
+
+/// The signature with the number, or the end of the signatures past the last one.
+fn key(number: u32) -> String {
+    if number < SIGNATURES {
+        format!("{}{:04}", SIGN, number)
+    } else {
+        AFTER_SIGN.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        newspaper::{Newspaper, Signature},
+        services::MockHost,
+    };
+
+    use super::CatalogFormat;
+
+    #[test]
+    fn csv() {
+        let page = super::export(&MockHost::default(), CatalogFormat::Csv, None).unwrap();
+
+        assert_eq!(
+            page.content,
+            "Сигнатура,Вестник,Начало на издаване,Край на издаване,Пн,Вт,Ср,Чт,Пт,Сб,Нд\n\
+             В1612,Труд,1946,,1,1,1,1,1,1,1\n\
+             В1616,Народен спор,1944,1989,1,0,0,1,0,1,0\n\
+             В4667,Орбита,1969,1991,0,0,0,0,0,1,0\n"
+        );
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn json_lines() {
+        // a newspaper stored before the namespaces were introduced
+        let storage = MockHost::default().with_record(
+            "В1111",
+            r#"{"signature":"В1111","name":"Утре","start_year":2030,"end_year":null,"weekly_schedule":[true,true,true,true,true,true,true]}"#,
+        );
+
+        let page = super::export(&storage, CatalogFormat::JsonLines, None).unwrap();
+        let lines: Vec<&str> = page.content.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with(r#"{"signature":"В1111","name":"Утре""#));
+        assert!(lines[3].starts_with(r#"{"signature":"В4667""#));
+    }
+
+    #[test]
+    fn skipped() {
+        let storage = MockHost::default()
+            .with_record("newspaper/В1000", "{")
+            .with_record("newspaper/В5000", "{");
+
+        let page = super::export(&storage, CatalogFormat::JsonLines, None).unwrap();
+        assert_eq!(page.content.lines().count(), 3);
+        assert_eq!(page.skipped, 2);
+    }
+
+    #[test]
+    fn pages() {
+        let storage = (1000..1250).fold(MockHost::default(), |storage, number| {
+            let signature = format!("В{}", number);
+            storage.with_newspaper(Newspaper::new_unchecked(
+                &signature,
+                "Вестник",
+                1990,
+                None,
+                [true; 7],
+            ))
+        });
+
+        let storage = storage.with_record("newspaper/В1150_broken", "{");
+
+        let mut cursor = None;
+        let mut rows = Vec::new();
+        let mut skipped = Vec::new();
+        loop {
+            let page = super::export(&storage, CatalogFormat::Csv, cursor.as_ref()).unwrap();
+            rows.extend(page.content.lines().map(str::to_string));
+            skipped.push(page.skipped);
+            match page.next_cursor {
+                Some(next) => cursor = Some(Signature::new(&next)),
+                None => break,
+            }
+        }
+
+        // the header, 250 newspapers and the 3 of the mock host
+        assert_eq!(rows.len(), 254);
+        assert!(rows[1].starts_with("В1000,"));
+        assert!(rows[101].starts_with("В1100,"));
+        assert!(rows[253].starts_with("В4667,"));
+        // the unreadable record is between the first two cursors
+        assert_eq!(skipped, vec![0, 1, 0]);
+    }
+}
@@ -0,0 +1,397 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use std::result::Result as StdResult;
+
+use crate::{
+    bindings,
+    newspaper::Date,
+    order::{Order, OrderNumber, PhoneNumber, RetentionPeriod},
+    repo::{ConfigRepo, OrderRepo},
+    response::Event,
+    Storage, Time,
+};
+
+use super::{
+    audit::{self, message, Change},
+    ServiceError,
+};
+
+/// Everything we hold about a customer. The orders that could not be read could not be checked
+/// either, so they are counted for `CheckIntegrity` to find them.
+#[derive(Serialize, JsonSchema)]
+pub(super) struct CustomerData {
+    orders: Vec<Order>,
+    unreadable: u32,
+}
+
+pub(super) fn export<S>(storage: &S, phone: &PhoneNumber) -> CustomerData
+where
+    S: Storage,
+{
+    let (orders, unreadable) = orders(storage);
+    CustomerData {
+        orders: orders
+            .into_iter()
+            .filter(|order| order.placed_by(phone))
+            .collect(),
+        unreadable,
+    }
+}
+
+/// Anonymizes all orders of the customer and emits an audit event with their public numbers.
+/// The phone itself is left out of the event, since it is the very data being erased.
+/// The unreadable orders are counted in the event, so that they do not hold back the erasure
+/// of the others.
+pub(super) fn erase<S, T>(
+    storage: &mut S,
+    clock: &T,
+    phone: &PhoneNumber,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let (orders, unreadable) = orders(storage);
+    anonymize::<S, T, _>(
+        storage,
+        clock,
+        message::ERASE_CUSTOMER_DATA,
+        orders.into_iter().filter(|order| order.placed_by(phone)),
+    )
+    .and_then(|numbers| {
+        super::emit_event::<S, T>(
+            storage,
+            clock,
+            &Event::customer_data_erased(numbers, unreadable),
+        )
+    })
+}
+
+/// Anonymizes the orders delivered before the configured retention period.
+/// Nothing is anonymized until a retention period is configured. The unreadable orders
+/// are counted in the event and left to `CheckIntegrity`, so that they do not hold back
+/// the anonymization of the others.
+pub(super) fn enforce_retention<S, T>(
+    storage: &mut S,
+    clock: &T,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let today = Date::from_millis(clock.now().timestamp);
+    let (orders, unreadable) = orders(storage);
+    retention_period(storage)
+        .and_then(|retention| match retention {
+            None => Ok(Vec::new()),
+            Some(retention) => anonymize::<S, T, _>(
+                storage,
+                clock,
+                message::ENFORCE_RETENTION,
+                orders
+                    .into_iter()
+                    .filter(|order| retention.expired(order, &today)),
+            ),
+        })
+        .and_then(|numbers| {
+            super::emit_event::<S, T>(
+                storage,
+                clock,
+                &Event::orders_anonymized(numbers, unreadable),
+            )
+        })
+}
+
+fn retention_period<S>(storage: &S) -> StdResult<Option<RetentionPeriod>, ServiceError>
+where
+    S: Storage,
+{
+    ConfigRepo::load(storage).map_err(ServiceError::Storage)
+}
+
+/// The readable orders along with how many could not be read.
+fn orders<S>(storage: &S) -> (Vec<Order>, u32)
+where
+    S: Storage,
+{
+    let (orders, unreadable): (Vec<_>, Vec<_>) =
+        OrderRepo::all(storage).into_iter().partition(Result::is_ok);
+    (
+        orders.into_iter().flatten().collect(),
+        u32::try_from(unreadable.len()).unwrap_or(u32::MAX),
+    )
+}
+
+/// Overwrites the orders with their anonymized version and returns their public numbers.
+/// The orders placed before they were numbered are keyed by their phone, so they are given
+/// a number and moved to the key of it. The storage cannot delete keys, so their old keys
+/// are left without a value.
+fn anonymize<S, T, I>(
+    storage: &mut S,
+    clock: &T,
+    message: &'static str,
+    orders: I,
+) -> StdResult<Vec<String>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+    I: Iterator<Item = Order>,
+{
+    orders
+        .filter(|order| !order.is_anonymized())
+        .map(|order| {
+            OrderRepo::id(storage, &order)
+                .map_err(ServiceError::Storage)
+                .and_then(|old_key| {
+                    numbered::<S, T>(storage, clock, order).and_then(|(order, number)| {
+                        let key = OrderRepo::key(&order, &number);
+                        let change =
+                            Change::order(storage, message, &old_key, Some(&number)).moved_to(&key);
+                        OrderRepo::save(storage, &key, &order.anonymize())
+                            .and_then(|()| OrderRepo::index_number(storage, &number, &key))
+                            .map(|()| {
+                                if key != old_key {
+                                    OrderRepo::remove(storage, &old_key);
+                                }
+                            })
+                            .map_err(ServiceError::Storage)
+                            .and_then(|()| audit::record::<S, T>(storage, clock, change))
+                            .map(|()| number.as_str().to_string())
+                    })
+                })
+        })
+        .collect()
+}
+
+/// The order along with its number, given one if it was placed before the numbers.
+fn numbered<S, T>(
+    storage: &mut S,
+    clock: &T,
+    order: Order,
+) -> StdResult<(Order, OrderNumber), ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    match order.number().cloned() {
+        Some(number) => Ok((order, number)),
+        None => super::next_order_number::<S, T>(storage, clock, order.year())
+            .map(|number| (order.with_number(number.clone()), number)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        newspaper::DateFormat,
+        order::{Order, OrderRequest, PhoneNumber, RetentionPeriod},
+        repo::{ConfigRepo, OrderRepo, ORDERS},
+        response::Event,
+        services::{MockClock, MockHost},
+        Storage, Time,
+    };
+
+    // 29-11-2024, the order is delivered on 04-12-2024
+    const NOW: u64 = 1732880395000;
+    // 10-12-2024
+    const LATER: u64 = 1733788800000;
+
+    #[test]
+    fn export() {
+        let storage = storage_with_order();
+
+        let data = super::export(&storage, &phone("0873528495"));
+        assert_eq!(data.orders.len(), 1);
+        assert_eq!(
+            data.orders[0].number(),
+            Some(&MockHost::order_number(2024, 1))
+        );
+
+        let data = super::export(&storage, &phone("0888123456"));
+        assert!(data.orders.is_empty());
+    }
+
+    #[test]
+    fn erase() {
+        let mut storage = storage_with_order();
+
+        let events = super::erase::<_, MockClock>(
+            &mut storage,
+            &MockClock::default(),
+            &phone("+359873528495"),
+        )
+        .unwrap();
+        assert_eq!(events[0].id, "dnevest_erase");
+        assert_eq!(
+            events[0].content,
+            Event::customer_data_erased(
+                vec![MockHost::order_number(2024, 1).as_str().to_string()],
+                0
+            )
+            .serialize(MockClock::default().now(), DateFormat::default())
+            .unwrap()
+        );
+
+        let data = super::export(&storage, &phone("0873528495"));
+        assert!(data.orders.is_empty());
+        assert_eq!(OrderRepo::all(&storage).len(), 1);
+        assert!(!storage.contains_text("873528495"));
+        assert!(!storage.contains_text("Тодор"));
+    }
+
+    #[test]
+    fn erase_order_keyed_by_phone() {
+        // placed before the orders were numbered
+        let mut storage = MockHost::default();
+        let order = order();
+        let legacy_key = order.identifier();
+        OrderRepo::save(&mut storage, &legacy_key, &order).unwrap();
+
+        let events =
+            super::erase::<_, MockClock>(&mut storage, &MockClock::default(), &phone("0873528495"))
+                .unwrap();
+        assert_eq!(
+            events[0].content,
+            Event::customer_data_erased(
+                vec![MockHost::order_number(2024, 1).as_str().to_string()],
+                0
+            )
+            .serialize(MockClock::default().now(), DateFormat::default())
+            .unwrap()
+        );
+
+        let orders = OrderRepo::all(&storage);
+        assert_eq!(orders.len(), 1);
+        assert!(orders[0].as_ref().unwrap().is_anonymized());
+        // the storage cannot delete the old key, only its value
+        assert_eq!(storage.retrieve(&ORDERS.key(&legacy_key)), Some(Vec::new()));
+        assert!(!storage.contains_text("Тодор"));
+    }
+
+    #[test]
+    fn erase_legacy_record() {
+        // written by the first release, with the phone as it was typed
+        let mut storage = MockHost::default().with_record(
+            &ORDERS.key("1732880395000_0873528495"),
+            r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"},"created_on_ms":1732880395000}"#,
+        );
+
+        let data = super::export(&storage, &phone("+359873528495"));
+        assert_eq!(data.orders.len(), 1);
+
+        let events = super::erase::<_, MockClock>(
+            &mut storage,
+            &MockClock::default(),
+            &phone("+359873528495"),
+        )
+        .unwrap();
+        assert_eq!(
+            events[0].content,
+            Event::customer_data_erased(
+                vec![MockHost::order_number(2024, 1).as_str().to_string()],
+                0
+            )
+            .serialize(MockClock::default().now(), DateFormat::default())
+            .unwrap()
+        );
+        // the storage cannot delete the old key, only its value
+        assert_eq!(
+            storage.retrieve(&ORDERS.key("1732880395000_0873528495")),
+            Some(Vec::new())
+        );
+        assert!(!storage.contains_text("Тодор"));
+    }
+
+    #[test]
+    fn erase_despite_unreadable_orders() {
+        let mut storage = storage_with_order().with_record(&ORDERS.key("broken"), "{");
+
+        let data = super::export(&storage, &phone("0873528495"));
+        assert_eq!((data.orders.len(), data.unreadable), (1, 1));
+
+        let events =
+            super::erase::<_, MockClock>(&mut storage, &MockClock::default(), &phone("0873528495"))
+                .unwrap();
+        assert_eq!(
+            events[0].content,
+            Event::customer_data_erased(
+                vec![MockHost::order_number(2024, 1).as_str().to_string()],
+                1
+            )
+            .serialize(MockClock::default().now(), DateFormat::default())
+            .unwrap()
+        );
+        assert!(!storage.contains_text("Тодор"));
+    }
+
+    #[test]
+    fn retention() {
+        let mut storage = storage_with_order();
+        let (now, later) = (MockClock::at(NOW), MockClock::at(LATER));
+        assert_eq!(
+            anonymized(&mut storage, &later),
+            expected(Vec::new(), 0, &later)
+        );
+
+        ConfigRepo::save(&mut storage, &RetentionPeriod::new(5)).unwrap();
+        assert_eq!(
+            anonymized(&mut storage, &now),
+            expected(Vec::new(), 0, &now)
+        );
+
+        let number = MockHost::order_number(2024, 1).as_str().to_string();
+        assert_eq!(
+            anonymized(&mut storage, &later),
+            expected(vec![number], 0, &later)
+        );
+        assert_eq!(
+            anonymized(&mut storage, &later),
+            expected(Vec::new(), 0, &later)
+        );
+    }
+
+    #[test]
+    fn retention_despite_unreadable_orders() {
+        let mut storage = storage_with_order().with_record(&ORDERS.key("broken"), "{");
+        ConfigRepo::save(&mut storage, &RetentionPeriod::new(5)).unwrap();
+        let later = MockClock::at(LATER);
+
+        let number = MockHost::order_number(2024, 1).as_str().to_string();
+        assert_eq!(
+            anonymized(&mut storage, &later),
+            expected(vec![number], 1, &later)
+        );
+    }
+
+    fn anonymized(storage: &mut MockHost, clock: &MockClock) -> Vec<u8> {
+        let events = super::enforce_retention::<_, MockClock>(storage, clock).unwrap();
+        assert_eq!(events[0].id, "dnevest_anon");
+        events[0].content.clone()
+    }
+
+    fn expected(numbers: Vec<String>, unreadable: u32, clock: &MockClock) -> Vec<u8> {
+        Event::orders_anonymized(numbers, unreadable)
+            .serialize(clock.now(), DateFormat::default())
+            .unwrap()
+    }
+
+    fn storage_with_order() -> MockHost {
+        let mut storage = MockHost::default();
+        crate::services::save_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+            .expect("failed to save the order");
+        storage
+    }
+
+    fn order() -> Order {
+        let json = r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
+        serde_json::from_str::<OrderRequest<MockHost>>(json)
+            .expect("failed to deserialize JSON")
+            .check(&MockHost::default(), &MockClock::default())
+            .expect("the order is valid")
+    }
+
+    fn phone(number: &str) -> PhoneNumber {
+        PhoneNumber::try_from(number.to_string()).unwrap()
+    }
+}
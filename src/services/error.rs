@@ -1,8 +1,11 @@
 use thiserror::Error;
 
-use crate::{bindings::ByteArray, newspaper, order};
+use crate::{bindings::ByteArray, newspaper, order, repo};
+
+use super::batch::Failure;
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Problem while serialization: {0}")]
     SerializationFault(serde_json::Error),
@@ -16,19 +19,53 @@ pub enum Error {
     #[error("Order domain error: {0}")]
     InvalidOrder(#[from] order::Error),
 
-    #[error("Problem while deserialization: {0}")]
-    DeserializationFault(serde_json::Error),
+    #[error("{0}")]
+    Storage(#[from] repo::Error),
 
     #[error("{0}")]
     NotFound(&'static str),
 
     #[error("Cannot create the order because it already exists")]
     DuplicateOrder,
+
+    #[error("Cannot number the order because all the order numbers are taken")]
+    OrderNumbersExhausted,
+
+    #[error("Invalid idempotency key: it must contain between 1 and 64 latin letters, digits, '-' or '_'")]
+    InvalidIdempotencyKey,
+
+    #[error("The idempotency key was already used for another request")]
+    ReusedIdempotencyKey,
+
+    #[error("The batch is rolled back because {} of its messages failed", .0.len())]
+    Batch(Vec<Failure>),
+
+    #[error("A batch cannot contain another batch")]
+    NestedBatch,
+
+    #[error("Cannot restore a snapshot of version {0}")]
+    UnsupportedSnapshot(u32),
+
+    #[error("The snapshot is corrupted, its checksum does not match its contents")]
+    CorruptedSnapshot,
+
+    #[error("A chunk of the snapshot holds at most {0} records")]
+    OversizedChunk(u16),
+
+    #[error("The snapshot contains an unknown record: {0}")]
+    UnknownRecord(String),
+
+    #[error("The snapshot contains a record under the id of another one: {0}")]
+    MisplacedRecord(String),
 }
 
 impl Error {
+    /// A failed batch lists the failed messages by their index in the batch.
     pub(crate) fn serialize(&self) -> ByteArray {
-        serde_json::to_vec(&self.to_string())
-            .unwrap_or(b"Error occurs while serializing error".to_vec())
+        match self {
+            Error::Batch(failures) => serde_json::to_vec(failures),
+            _ => serde_json::to_vec(&self.to_string()),
+        }
+        .unwrap_or(b"Error occurs while serializing error".to_vec())
     }
 }
@@ -0,0 +1,248 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use std::{borrow::Cow, result::Result as StdResult};
+
+use crate::{
+    bindings::{self, ByteArray},
+    repo::{self, Record, IDEMPOTENCY},
+    Storage,
+};
+
+use super::ServiceError;
+
+const MAX_KEY_LEN: usize = 64;
+/// The invariant in the regular expressions of JSON Schema.
+const PATTERN: &str = "^[A-Za-z0-9_-]{1,64}$";
+/// How long a client can safely retry a request with the same key.
+const EXPIRY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// A client-generated key, e.g. a UUID, sent along with a request so that
+/// a retried or double-submitted request is executed only once.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, JsonSchema)]
+#[serde(try_from = "String")]
+#[schemars(extend("pattern" = PATTERN))]
+pub(crate) struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    fn invariant_held(&self) -> StdResult<(), ServiceError> {
+        if !self.0.is_empty()
+            && self.0.len() <= MAX_KEY_LEN
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Ok(())
+        } else {
+            Err(ServiceError::InvalidIdempotencyKey)
+        }
+    }
+}
+
+impl TryFrom<String> for IdempotencyKey {
+    type Error = ServiceError;
+
+    fn try_from(value: String) -> StdResult<Self, Self::Error> {
+        let obj = Self(value);
+        obj.invariant_held().map(|()| obj)
+    }
+}
+
+/// A request along with the fingerprint of its content, so that a key reused for another request
+/// is told apart from a retry. The content is fingerprinted in canonical form, with its fields
+/// sorted and without whitespace, so that a retry is recognized however the client writes it.
+pub(crate) struct Fingerprinted<V> {
+    value: V,
+    fingerprint: String,
+}
+
+impl<V> Fingerprinted<V> {
+    pub(super) fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub(super) fn into_inner(self) -> V {
+        self.value
+    }
+}
+
+impl<'de, V> Deserialize<'de> for Fingerprinted<V>
+where
+    V: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer).and_then(|content| {
+            let fingerprint = serde_json::to_vec(&content)
+                .map(|canonical| repo::fingerprint(&canonical))
+                .map_err(de::Error::custom)?;
+            V::deserialize(content)
+                .map(|value| Self { value, fingerprint })
+                .map_err(de::Error::custom)
+        })
+    }
+}
+
+/// The same schema as the request itself.
+impl<V> JsonSchema for Fingerprinted<V>
+where
+    V: JsonSchema,
+{
+    fn inline_schema() -> bool {
+        V::inline_schema()
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        V::schema_name()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        V::schema_id()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        V::json_schema(generator)
+    }
+}
+
+/// The outcome of the first request made with an idempotency key.
+/// The event is kept as it was published, so that a retry gets exactly the same response.
+#[derive(Serialize, Deserialize)]
+pub(super) struct Replay {
+    event_id: String,
+    content: ByteArray,
+    expires_on_ms: u64,
+    /// The fingerprint of the request, see `Fingerprinted`.
+    request: String,
+}
+
+impl Record for Replay {}
+
+impl Replay {
+    /// Returns the outcome of the previous request with this key, if it has not expired yet.
+    pub(super) fn find<S>(
+        storage: &S,
+        key: &IdempotencyKey,
+        now_ms: u64,
+    ) -> StdResult<Option<Self>, ServiceError>
+    where
+        S: Storage,
+    {
+        IDEMPOTENCY
+            .load::<_, Self>(storage, &key.0)
+            .map_err(ServiceError::Storage)
+            .map(|replay| replay.filter(|replay| replay.expires_on_ms > now_ms))
+    }
+
+    pub(super) fn save<S>(
+        storage: &mut S,
+        key: &IdempotencyKey,
+        request: &str,
+        event: &bindings::Event,
+        now_ms: u64,
+    ) -> StdResult<(), ServiceError>
+    where
+        S: Storage,
+    {
+        let replay = Self {
+            event_id: event.id.clone(),
+            content: event.content.clone(),
+            expires_on_ms: now_ms + EXPIRY_MS,
+            request: request.to_string(),
+        };
+        IDEMPOTENCY
+            .save(storage, &key.0, &replay)
+            .map_err(ServiceError::Storage)
+    }
+
+    /// The events of the first request, if it is retried, and an error if the key is reused
+    /// for another request.
+    pub(super) fn events(self, request: &str) -> StdResult<Vec<bindings::Event>, ServiceError> {
+        if self.request == request {
+            Ok(vec![bindings::Event {
+                id: self.event_id,
+                content: self.content,
+            }])
+        } else {
+            Err(ServiceError::ReusedIdempotencyKey)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{bindings, services::MockHost};
+
+    use serde_json::Value;
+
+    use super::{Fingerprinted, IdempotencyKey, Replay, EXPIRY_MS};
+
+    const NOW: u64 = 1732880395000;
+
+    #[test]
+    fn valid_keys() {
+        assert!(key("3f2b9c4e-7a1d-4e8b-9c0a-5d6e7f8a9b0c").is_ok());
+        assert!(key("order_42").is_ok());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let msg = "Invalid idempotency key";
+        assert_err(key(""), msg);
+        assert_err(key("key with spaces"), msg);
+        assert_err(key("ключ"), msg);
+        assert_err(key(&"a".repeat(65)), msg);
+    }
+
+    #[test]
+    fn replay_until_expired() {
+        let mut storage = MockHost::default();
+        let key = key("3f2b9c4e").unwrap();
+        let event = bindings::Event {
+            id: "dnevest_n_o".to_string(),
+            content: b"{}".to_vec(),
+        };
+        let request = fingerprinted(r#"{"a":1,"b":[2,3]}"#);
+        Replay::save(&mut storage, &key, request.fingerprint(), &event, NOW).unwrap();
+
+        let replay = Replay::find(&storage, &key, NOW + 1)
+            .unwrap()
+            .expect("the key has not expired");
+        assert_eq!(
+            replay.events(request.fingerprint()).unwrap()[0].content,
+            b"{}"
+        );
+
+        assert!(Replay::find(&storage, &key, NOW + EXPIRY_MS)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn canonical_fingerprint() {
+        let request = fingerprinted(r#"{"a":1,"b":[2,3]}"#);
+        let reordered = fingerprinted(r#"{ "b": [2, 3], "a": 1 }"#);
+        assert_eq!(request.fingerprint(), reordered.fingerprint());
+        assert_ne!(
+            request.fingerprint(),
+            fingerprinted(r#"{"a":1,"b":[3,2]}"#).fingerprint()
+        );
+    }
+
+    fn fingerprinted(json: &str) -> Fingerprinted<Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn key(value: &str) -> Result<IdempotencyKey, super::ServiceError> {
+        IdempotencyKey::try_from(value.to_string())
+    }
+
+    fn assert_err(r: Result<IdempotencyKey, super::ServiceError>, msg: &str) {
+        assert!(r.expect_err("expected an error").to_string().contains(msg))
+    }
+}
@@ -0,0 +1,230 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::result::Result as StdResult;
+
+use crate::{
+    bindings,
+    newspaper::{self, csv, Newspaper, UncheckedNewspaper},
+    repo::{NewspaperRepo, NEWSPAPERS},
+    response::Event,
+    Storage, Time,
+};
+
+use super::{
+    audit::{self, message, Change},
+    ServiceError,
+};
+
+/// Whether the newspapers already in the catalog are overwritten or reported as failed.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Default, PartialEq, Clone, Copy, JsonSchema)]
+pub(crate) enum ImportMode {
+    #[default]
+    InsertOnly,
+    Upsert,
+}
+
+/// What happened to a row of the CSV, numbered from 1 along with the header.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct ImportedRow {
+    row: usize,
+    outcome: Outcome,
+}
+
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, JsonSchema)]
+enum Outcome {
+    Created(Newspaper),
+    Updated(Newspaper),
+    /// Stored, but the audit log could not record it.
+    Unaudited {
+        newspaper: Newspaper,
+        error: String,
+    },
+    Failed(String),
+}
+
+/// Imports every valid row and reports the invalid ones, so that they can be fixed
+/// and imported again.
+pub(super) fn import<S, T>(
+    storage: &mut S,
+    clock: &T,
+    csv: &str,
+    mode: ImportMode,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    csv::parse(csv)
+        .map_err(ServiceError::DomainError)
+        .and_then(|rows| {
+            let report = rows
+                .into_iter()
+                .map(|(row, newspaper)| ImportedRow {
+                    row,
+                    outcome: import_row::<S, T>(storage, clock, newspaper, mode),
+                })
+                .collect();
+            super::emit_event::<S, T>(storage, clock, &Event::newspapers_imported(report))
+        })
+}
+
+fn import_row<S, T>(
+    storage: &mut S,
+    clock: &T,
+    newspaper: StdResult<UncheckedNewspaper, newspaper::Error>,
+    mode: ImportMode,
+) -> Outcome
+where
+    S: Storage,
+    T: Time,
+{
+    newspaper
+        .and_then(|newspaper| newspaper.into_checked(clock.now()))
+        .map_err(ServiceError::DomainError)
+        .and_then(|newspaper| {
+            let exists = NewspaperRepo::contains(storage, newspaper.identificator());
+            if exists && mode == ImportMode::InsertOnly {
+                return Err(ServiceError::DuplicateSignature);
+            }
+            let change = Change::new(
+                storage,
+                message::IMPORT_NEWSPAPERS,
+                &NEWSPAPERS,
+                newspaper.identificator(),
+            );
+            NewspaperRepo::save(storage, &newspaper)
+                .map_err(ServiceError::Storage)
+                .map(|()| match audit::record::<S, T>(storage, clock, change) {
+                    Ok(()) if exists => Outcome::Updated(newspaper),
+                    Ok(()) => Outcome::Created(newspaper),
+                    Err(error) => Outcome::Unaudited {
+                        newspaper,
+                        error: error.to_string(),
+                    },
+                })
+        })
+        .unwrap_or_else(|error| Outcome::Failed(error.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        repo::{CounterRepo, NewspaperRepo, COUNTERS},
+        services::{MockClock, MockHost, ServiceError},
+        Time,
+    };
+
+    use super::{ImportMode, Outcome};
+
+    const CSV: &str = "signature,name,start_year,end_year,Пн,Вт,Ср,Чт,Пт,Сб,Нд\n\
+                       В1905,Поглед,1966,1996,1,0,0,0,0,0,0\n\
+                       В1612,Труд,1946,2024,1,1,1,1,1,1,1\n\
+                       В1111,Утре,2030,,1,1,1,1,1,1,1\n";
+
+    #[test]
+    fn insert_only() {
+        let mut storage = MockHost::default();
+        let events = super::import::<_, MockClock>(
+            &mut storage,
+            &MockClock::default(),
+            CSV,
+            ImportMode::InsertOnly,
+        )
+        .unwrap();
+
+        assert_eq!(events[0].id, "dnevest_import");
+        let report = report(&events[0].content);
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0]["row"], 2);
+        assert_eq!(report[0]["outcome"]["Created"]["signature"], "В1905");
+        assert!(report[1]["outcome"]["Failed"]
+            .as_str()
+            .unwrap()
+            .contains("signature already exists"));
+        assert!(report[2]["outcome"]["Failed"]
+            .as_str()
+            .unwrap()
+            .contains("cannot be in the future"));
+
+        assert!(NewspaperRepo::contains(&storage, "В1905"));
+        assert!(!NewspaperRepo::contains(&storage, "В1111"));
+    }
+
+    #[test]
+    fn upsert() {
+        let mut storage = MockHost::default();
+        super::import::<_, MockClock>(&mut storage, &MockClock::default(), CSV, ImportMode::Upsert)
+            .unwrap();
+
+        let trud = NewspaperRepo::find(&storage, "В1612")
+            .unwrap()
+            .unwrap()
+            .into_checked(MockClock::default().now())
+            .unwrap();
+        assert_eq!(trud.end_year(), Some(2024));
+    }
+
+    #[test]
+    fn later_clock() {
+        // 01.01.2031
+        let clock = MockClock::at(1_924_992_000_000);
+        let mut storage = MockHost::default();
+        super::import::<_, MockClock>(&mut storage, &clock, CSV, ImportMode::InsertOnly).unwrap();
+
+        assert!(NewspaperRepo::contains(&storage, "В1111"));
+    }
+
+    #[test]
+    fn outcome() {
+        assert!(matches!(
+            super::import_row::<_, MockClock>(
+                &mut MockHost::default(), &MockClock::default(),
+                Err(crate::newspaper::Error::ColumnCount(3)),
+                ImportMode::Upsert,
+            ),
+            Outcome::Failed(error) if error.contains("found 3")
+        ));
+    }
+
+    #[test]
+    fn unaudited() {
+        let mut storage = MockHost::default().with_record(&COUNTERS.key(CounterRepo::AUDIT), "{");
+        let events = super::import::<_, MockClock>(
+            &mut storage,
+            &MockClock::default(),
+            CSV,
+            ImportMode::InsertOnly,
+        )
+        .unwrap();
+
+        let report = report(&events[0].content);
+        assert_eq!(
+            report[0]["outcome"]["Unaudited"]["newspaper"]["signature"],
+            "В1905"
+        );
+        assert!(NewspaperRepo::contains(&storage, "В1905"));
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let res = super::import::<_, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            "В1905,\"Поглед",
+            ImportMode::Upsert,
+        );
+        assert!(matches!(res, Err(ServiceError::DomainError(_))));
+    }
+
+    fn report(content: &[u8]) -> Vec<serde_json::Value> {
+        let published: serde_json::Value = serde_json::from_slice(content).unwrap();
+        published["event"]["NewspapersImported"]["rows"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+}
@@ -0,0 +1,362 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use std::result::Result as StdResult;
+
+use crate::{
+    bindings::component::dnevest::time::Clock,
+    newspaper::{DateFormat, UncheckedNewspaper},
+    order::{DeliverySchedule, MaxCards, Order, OrderNumber, PriceList, RetentionPeriod},
+    repo::{
+        self, Config, CounterRepo, Namespace, NewspaperRepo, OrderRepo, Record, Unreadable, AUDIT,
+        CONFIG, COUNTERS, IDEMPOTENCY, NEWSPAPERS, ORDERS, ORDER_NUMBERS,
+    },
+    Storage,
+};
+
+use super::{audit::AuditEntry, idempotency::Replay};
+
+/// The problems found in the stored records, so that they can be fixed
+/// before they surface in the responses to the customers.
+#[derive(Serialize, JsonSchema)]
+pub(super) struct IntegrityReport {
+    checked: u32,
+    problems: Vec<Problem>,
+}
+
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, JsonSchema)]
+struct Problem {
+    namespace: &'static str,
+    /// The id of the record, or the fingerprint of its contents, as in the audit log,
+    /// if the id is not known or holds personal data.
+    record: String,
+    problem: String,
+}
+
+impl Problem {
+    fn new(namespace: &Namespace, record: &str, problem: String) -> Self {
+        Self {
+            namespace: namespace.name(),
+            record: record.to_string(),
+            problem,
+        }
+    }
+
+    fn unreadable(namespace: &Namespace, unreadable: Unreadable) -> Self {
+        Self::new(
+            namespace,
+            &unreadable.fingerprint,
+            unreadable.error.to_string(),
+        )
+    }
+}
+
+pub(super) fn check<S>(storage: &S, now: Clock) -> IntegrityReport
+where
+    S: Storage,
+{
+    let checks = [
+        newspapers(storage, now),
+        orders(storage),
+        order_numbers(storage),
+        replays(storage),
+        config(storage),
+        counters(storage),
+        audit_log(storage),
+    ];
+    checks.into_iter().fold(
+        IntegrityReport {
+            checked: 0,
+            problems: Vec::new(),
+        },
+        |mut report, (checked, problems)| {
+            report.checked += checked;
+            report.problems.extend(problems);
+            report
+        },
+    )
+}
+
+/// Every newspaper has to be readable and hold its invariants,
+/// otherwise it silently disappears from the searches.
+fn newspapers<S>(storage: &S, now: Clock) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    inspect(storage, &NEWSPAPERS, |newspaper: UncheckedNewspaper| {
+        let signature = newspaper.signature().to_string();
+        newspaper
+            .into_checked(now)
+            .err()
+            .map(|error| Problem::new(&NEWSPAPERS, &signature, error.to_string()))
+            .into_iter()
+            .collect()
+    })
+}
+
+/// Every cover has to be printed from a newspaper in the catalog,
+/// and every public number has to lead back to its order.
+fn orders<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    inspect(storage, &ORDERS, |order: Order| {
+        // the key of the orders placed before the numbers holds the phone
+        let record = order.number().map_or_else(
+            || {
+                ORDERS
+                    .fingerprint(storage, &order.identifier())
+                    .unwrap_or_default()
+            },
+            |number| number.as_str().to_string(),
+        );
+        let dangling = order
+            .signatures()
+            .filter(|signature| !NewspaperRepo::contains(storage, signature))
+            .map(|signature| {
+                Problem::new(
+                    &ORDERS,
+                    &record,
+                    format!("The cover signature {} is not in the catalog", signature),
+                )
+            });
+        let unindexed = order
+            .number()
+            .filter(|number| !indexed(storage, number))
+            .map(|_| {
+                Problem::new(
+                    &ORDERS,
+                    &record,
+                    "The order cannot be found by its number".to_string(),
+                )
+            });
+        dangling.chain(unindexed).collect()
+    })
+}
+
+fn indexed<S>(storage: &S, number: &OrderNumber) -> bool
+where
+    S: Storage,
+{
+    OrderRepo::id_by_number(storage, number)
+        .ok()
+        .flatten()
+        .is_some_and(|id| OrderRepo::contains(storage, &id))
+}
+
+fn order_numbers<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    inspect(storage, &ORDER_NUMBERS, |id: String| {
+        (!OrderRepo::contains(storage, &id))
+            .then(|| {
+                let record = OrderRepo::number_in_key(&id)
+                    .map_or_else(|| repo::fingerprint(id.as_bytes()), str::to_string);
+                Problem::new(
+                    &ORDER_NUMBERS,
+                    &record,
+                    "The number leads to a missing order".to_string(),
+                )
+            })
+            .into_iter()
+            .collect()
+    })
+}
+
+fn replays<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    inspect(storage, &IDEMPOTENCY, |_: Replay| Vec::new())
+}
+
+/// The settings are optional, except for the max number of cards,
+/// without which no order can be placed.
+fn config<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    let problems = [
+        setting::<S, MaxCards>(storage, true),
+        setting::<S, DeliverySchedule>(storage, false),
+        setting::<S, RetentionPeriod>(storage, false),
+        setting::<S, DateFormat>(storage, false),
+        setting::<S, PriceList>(storage, false),
+    ];
+    (
+        u32::try_from(problems.len()).expect("a few settings"),
+        problems.into_iter().flatten().collect(),
+    )
+}
+
+fn setting<S, C>(storage: &S, required: bool) -> Option<Problem>
+where
+    S: Storage,
+    C: Config,
+{
+    match CONFIG.load::<_, C>(storage, C::NAME) {
+        Ok(Some(_)) => None,
+        Ok(None) => required.then(|| {
+            Problem::new(
+                &CONFIG,
+                C::NAME,
+                "The setting is required, but not configured".to_string(),
+            )
+        }),
+        Err(error) => Some(Problem::new(&CONFIG, C::NAME, error.to_string())),
+    }
+}
+
+fn counters<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    let counters = [CounterRepo::ORDERS, CounterRepo::AUDIT];
+    let problems = counters
+        .iter()
+        .filter_map(|name| {
+            COUNTERS
+                .load::<_, u64>(storage, name)
+                .err()
+                .map(|error| Problem::new(&COUNTERS, name, error.to_string()))
+        })
+        .collect();
+    (
+        u32::try_from(counters.len()).expect("a few counters"),
+        problems,
+    )
+}
+
+fn audit_log<S>(storage: &S) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+{
+    inspect(storage, &AUDIT, |_: AuditEntry| Vec::new())
+}
+
+/// Checks every readable record of the namespace and reports the unreadable ones.
+fn inspect<S, V, F>(storage: &S, namespace: &Namespace, check: F) -> (u32, Vec<Problem>)
+where
+    S: Storage,
+    V: Record,
+    F: Fn(V) -> Vec<Problem>,
+{
+    let records: Vec<StdResult<V, Unreadable>> = namespace.inspect(storage);
+    let checked = u32::try_from(records.len()).expect("too many records");
+    let problems = records
+        .into_iter()
+        .flat_map(|record| match record {
+            Ok(record) => check(record),
+            Err(unreadable) => vec![Problem::unreadable(namespace, unreadable)],
+        })
+        .collect();
+    (checked, problems)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        order::{Order, OrderRequest},
+        repo::{self, OrderRepo, NEWSPAPERS, ORDERS},
+        services::{MockClock, MockHost},
+        Storage, Time,
+    };
+
+    #[test]
+    fn no_problems() {
+        let mut storage = MockHost::default();
+        crate::services::save_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+            .expect("failed to save the order");
+
+        let report = super::check(&storage, MockClock::default().now());
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        // 3 newspapers, 1 order, 1 order number, 5 settings, 2 counters and 1 audit entry
+        assert_eq!(report.checked, 13);
+    }
+
+    #[test]
+    fn problems() {
+        let mut storage = MockHost::default()
+            .with_record(
+                "newspaper/В1111",
+                r#"{"version":1,"data":{"signature":"В1111","name":"Утре","start_year":2030,"end_year":null,"weekly_schedule":[true,true,true,true,true,true,true]}}"#,
+            )
+            .with_record("newspaper/В2222", "{\"broken\":")
+            .with_record(
+                "newspaper/В1616",
+                r#"{"version":1,"data":{"signature":"В1616","name":"Народен спорт","start_year":1944,"end_year":1989,"weekly_schedule":"daily"}}"#,
+            )
+            .with_record("config/delivery_schedule", r#"{"version":1,"data":{}}"#);
+        crate::services::save_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+            .expect("failed to save the order");
+
+        let report = super::check(&storage, MockClock::default().now());
+        let problems: Vec<_> = report
+            .problems
+            .iter()
+            .map(|problem| (problem.namespace, problem.record.as_str()))
+            .collect();
+        assert_eq!(problems.len(), 4, "{:?}", report.problems);
+        assert!(problems.contains(&("newspaper", "В1111")));
+        assert!(problems.contains(&("newspaper", repo::fingerprint(b"{\"broken\":").as_str())));
+        assert!(problems.contains(&("config", "delivery_schedule")));
+        assert!(problems.contains(&(
+            "newspaper",
+            NEWSPAPERS.fingerprint(&storage, "В1616").unwrap().as_str()
+        )));
+    }
+
+    #[test]
+    fn no_personal_data() {
+        let mut storage = MockHost::default();
+        let order = order();
+        let key = order.identifier();
+        ORDERS.save(&mut storage, &key, &order).unwrap();
+        // an unreadable order, and a number of a missing order keyed by the phone
+        storage.persist(
+            "order/1732880395001_+359888123456",
+            &br#"{"phone":"+359888123456""#.to_vec(),
+        );
+        OrderRepo::index_number(
+            &mut storage,
+            &MockHost::order_number(2024, 1),
+            "1732880395002_+359888123456",
+        )
+        .unwrap();
+        // the order refers to В1616 and В4667
+        storage.remove("newspaper/В4667");
+
+        let report = super::check(&storage, MockClock::default().now());
+        assert_eq!(report.problems.len(), 3, "{:?}", report.problems);
+        assert!(report.problems.iter().all(|problem| {
+            !problem.record.contains("359") && !problem.problem.contains("359")
+        }));
+    }
+
+    #[test]
+    fn dangling_signature() {
+        let mut storage = MockHost::default();
+        crate::services::save_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+            .expect("failed to save the order");
+        // the order refers to В1616 and В4667
+        storage.remove("newspaper/В4667");
+
+        let report = super::check(&storage, MockClock::default().now());
+        assert!(report
+            .problems
+            .iter()
+            .any(|problem| problem.namespace == "order"
+                && problem.problem.contains("В4667")
+                && problem.record.starts_with("DN-2024-")));
+    }
+
+    fn order() -> Order {
+        let json = r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
+        serde_json::from_str::<OrderRequest<MockHost>>(json)
+            .expect("failed to deserialize JSON")
+            .check(&MockHost::default(), &MockClock::default())
+            .expect("the order is valid")
+    }
+}
@@ -1,27 +1,68 @@
-#[cfg(test)]
-use std::collections::HashMap;
+use std::{collections::BTreeMap, ops::Bound};
 
-use crate::Time;
-#[cfg(test)]
 use crate::{
     bindings::{component::dnevest::time::Clock, ByteArray},
-    Storage,
+    newspaper::{Newspaper, Year},
+    order::{MaxCards, NumberKey, OrderNumber},
+    repo::{ConfigRepo, NewspaperRepo},
+    Storage, Time,
 };
 
-#[cfg(test)]
-use super::Newspaper;
+pub(crate) const CURRENT_YEAR: Year = 2024;
+/// 29.11.2024, 11:39:55 UTC
+const NOW_MS: u64 = 1732880395000;
 
-#[cfg(test)]
-pub(crate) const CURRENT_YEAR: crate::newspaper::Year = 2024;
-
-#[cfg(test)]
+/// An in-memory storage with the semantics of the host: the keys are compared byte by byte,
+/// and a range includes its start and excludes its end.
 #[derive(Debug)]
 pub(crate) struct MockHost {
-    store: HashMap<String, ByteArray>,
+    store: BTreeMap<String, ByteArray>,
 }
 
-#[cfg(test)]
 impl MockHost {
+    /// A storage without any records, not even the settings.
+    pub(crate) fn empty() -> Self {
+        Self {
+            store: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn with_max_cards(mut self, max_number: u8) -> Self {
+        ConfigRepo::save(&mut self, &MaxCards::new(max_number))
+            .expect("Failed to serialize max_cards");
+        self
+    }
+
+    pub(crate) fn with_newspaper(mut self, newspaper: Newspaper) -> Self {
+        NewspaperRepo::save(&mut self, &newspaper).expect("Failed to serialize Newspaper");
+        self
+    }
+
+    /// A record stored as it is, e.g. before the namespaces or the versions were introduced.
+    pub(crate) fn with_record(mut self, key: &str, record: &str) -> Self {
+        self.persist(key, &record.as_bytes().to_vec());
+        self
+    }
+
+    /// The host cannot delete records, but the tests need to simulate missing ones.
+    pub(crate) fn remove(&mut self, key: &str) {
+        self.store.remove(key);
+    }
+
+    /// Whether the text appears in any key or value, e.g. to check that some data is erased.
+    pub(crate) fn contains_text(&self, text: &str) -> bool {
+        self.store
+            .iter()
+            .any(|(key, value)| key.contains(text) || String::from_utf8_lossy(value).contains(text))
+    }
+
+    /// The number of the order with the sequence, when the first order is numbered
+    /// at the default time of the clock.
+    pub(crate) fn order_number(year: Year, sequence: u64) -> OrderNumber {
+        OrderNumber::new(year, sequence, &NumberKey::from_time(NOW_MS))
+            .expect("The sequence fits in the order numbers")
+    }
+
     fn load_newspapers() -> Vec<Newspaper> {
         vec![
             Newspaper::new_unchecked(
@@ -49,24 +90,15 @@ impl MockHost {
     }
 }
 
+/// The max cards and a few newspapers, which most of the tests rely on.
 impl Default for MockHost {
     fn default() -> Self {
-        let mut host = Self {
-            store: Default::default(),
-        };
-
-        let max_cards = serde_json::to_vec(&40).expect("Failed to serialize max_cards");
-        host.persist("max_cards", &max_cards);
-
-        Self::load_newspapers().into_iter().for_each(|newspaper| {
-            let serialized = serde_json::to_vec(&newspaper).expect("Failed to serialize Newspaper");
-            host.persist(newspaper.identificator(), &serialized);
-        });
-        host
+        Self::load_newspapers()
+            .into_iter()
+            .fold(Self::empty().with_max_cards(40), Self::with_newspaper)
     }
 }
 
-#[cfg(test)]
 impl Storage for MockHost {
     fn persist(&mut self, key: &str, value: &ByteArray) {
         self.store.insert(key.to_string(), value.clone());
@@ -76,16 +108,94 @@ impl Storage for MockHost {
         self.store.get(key).cloned()
     }
 
-    fn retrieve_range(&self, _start: &str, _end: &str) -> Vec<ByteArray> {
-        self.store.values().cloned().collect()
+    fn retrieve_range(&self, start: &str, end: &str) -> Vec<ByteArray> {
+        if start >= end {
+            return Vec::new();
+        }
+        self.store
+            .range::<str, _>((Bound::Included(start), Bound::Excluded(end)))
+            .map(|(_, value)| value.clone())
+            .collect()
     }
 }
 
-#[cfg(test)]
-impl Time for MockHost {
-    fn now() -> Clock {
+/// A clock stopped at an instant, by default at the time the examples were taken.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MockClock {
+    timestamp: u64,
+}
+
+impl MockClock {
+    pub(crate) fn at(timestamp: u64) -> Self {
+        Self { timestamp }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::at(NOW_MS)
+    }
+}
+
+impl Time for MockClock {
+    fn now(&self) -> Clock {
         Clock {
-            timestamp: 1732880395,
+            timestamp: self.timestamp,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{Storage, Time};
+
+    use super::{MockClock, MockHost};
+
+    #[test]
+    fn range_boundaries() {
+        let host = ["a", "a/1", "a/2", "a0", "b"]
+            .into_iter()
+            .fold(MockHost::empty(), |host, key| host.with_record(key, key));
+
+        assert_eq!(
+            host.retrieve_range("a/", "a0"),
+            vec![b"a/1".to_vec(), b"a/2".to_vec()]
+        );
+        assert_eq!(host.retrieve_range("a/2", "b").len(), 2);
+        assert!(host.retrieve_range("a0", "a0").is_empty());
+        assert!(host.retrieve_range("b", "a").is_empty());
+    }
+
+    #[test]
+    fn byte_wise_order() {
+        // the Cyrillic letters are encoded in more bytes, after all the Latin ones
+        let host = ["В1612", "Z", "a", "Г1"]
+            .into_iter()
+            .fold(MockHost::empty(), |host, key| host.with_record(key, key));
+
+        assert_eq!(
+            host.retrieve_range("A", "В"),
+            vec![b"Z".to_vec(), b"a".to_vec()]
+        );
+        assert_eq!(
+            host.retrieve_range("В", "Г"),
+            vec!["В1612".as_bytes().to_vec()]
+        );
+    }
+
+    #[test]
+    fn seeded_records() {
+        let host = MockHost::default();
+        assert_eq!(host.retrieve_range("newspaper/", "newspaper0").len(), 3);
+        assert_eq!(host.retrieve_range("config/", "config0").len(), 1);
+        assert!(MockHost::empty()
+            .retrieve_range("", "\u{10FFFF}")
+            .is_empty());
+    }
+
+    #[test]
+    fn clock() {
+        assert_eq!(MockClock::default().now().timestamp, super::NOW_MS);
+        assert_eq!(MockClock::at(0).now().timestamp, 0);
+    }
+}
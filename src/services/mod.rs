@@ -1,237 +1,893 @@
-use serde::Serialize;
-
 use std::result::Result as StdResult;
 
 use crate::{
     bindings::{self, ByteArray},
-    newspaper::{self, Date, Newspaper, Signature, Year},
-    order::{MaxCards, Order, OrderRequest},
+    msgs::{ExecuteMsg, QueryMsg},
+    newspaper::{self, Date, DateFormat, NewspaperRequest, Signature, Year},
+    order::{
+        DeliverySchedule, MaxCards, NumberKey, Order, OrderNumber, OrderRequest, PhoneNumber,
+        PriceList, RetentionPeriod,
+    },
+    repo::{self, Config, ConfigRepo, CounterRepo, NewspaperRepo, OrderRepo, CONFIG, NEWSPAPERS},
     response::Event,
     Storage, Time,
 };
 
+mod audit;
+mod batch;
+mod catalog;
+mod customer_data;
 mod error;
+mod idempotency;
+mod import;
+mod integrity;
+mod schema;
+mod snapshot;
 
 #[cfg(test)]
 pub(crate) mod mock_host;
 
+use audit::{message, Change};
+pub(crate) use catalog::CatalogFormat;
 pub(super) use error::Error as ServiceError;
+use idempotency::Replay;
+pub(crate) use idempotency::{Fingerprinted, IdempotencyKey};
+pub(crate) use import::{ImportMode, ImportedRow};
 #[cfg(test)]
-pub(crate) use mock_host::MockHost;
+pub(crate) use mock_host::{MockClock, MockHost};
+pub(crate) use snapshot::Chunk;
 
-pub(crate) fn create_newspaper<S>(input: Newspaper) -> StdResult<Vec<bindings::Event>, ByteArray>
+pub(crate) fn execute<S, T>(
+    msg: ExecuteMsg<S>,
+    clock: &T,
+) -> StdResult<Vec<bindings::Event>, ByteArray>
 where
     S: Storage + Default,
+    T: Time,
 {
-    self::new_newspaper::<S>(input).map_err(|error| error.serialize())
+    let mut storage = S::default();
+    match msg {
+        ExecuteMsg::Batch(msgs) => batch::run::<S, S, T>(&mut storage, clock, msgs),
+        msg => self::apply::<S, S, T>(&mut storage, clock, msg),
+    }
+    .map_err(|error| error.serialize())
 }
 
-pub(crate) fn add_final_year<S, T>(
-    signature: Signature,
-    final_year: Year,
-) -> StdResult<Vec<bindings::Event>, ByteArray>
+/// Executes a single message over the storage. The orders are checked against the catalog
+/// and the settings of the same storage, so that within a batch they see the changes of
+/// the previous messages.
+fn apply<S, H, T>(
+    storage: &mut S,
+    clock: &T,
+    msg: ExecuteMsg<H>,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    H: Storage + Default,
+    T: Time,
+{
+    match msg {
+        ExecuteMsg::CreateNewspaper { input } => self::new_newspaper::<S, T>(storage, clock, input),
+        ExecuteMsg::AddFinalYear {
+            signature,
+            final_year,
+        } => self::define_end_year::<S, T>(storage, clock, signature.as_str(), final_year),
+        ExecuteMsg::SpecifyMaxCards { max_number } => {
+            self::configure_max_cards::<S, T>(storage, clock, max_number)
+        }
+        ExecuteMsg::SpecifyDeliverySchedule { schedule } => {
+            self::configure_delivery_schedule::<S, T>(storage, clock, schedule)
+        }
+        ExecuteMsg::SpecifyPriceList { prices } => {
+            self::configure_price_list::<S, T>(storage, clock, prices)
+        }
+        ExecuteMsg::CreateOrder {
+            order,
+            idempotency_key,
+        } => self::create_order::<S, H, T>(storage, clock, order, idempotency_key),
+        ExecuteMsg::SpecifyRetention { days } => {
+            self::configure_retention::<S, T>(storage, clock, days)
+        }
+        ExecuteMsg::SpecifyDateFormat { format } => {
+            self::configure_date_format::<S, T>(storage, clock, format)
+        }
+        ExecuteMsg::EraseCustomerData { phone } => {
+            customer_data::erase::<S, T>(storage, clock, &phone)
+        }
+        ExecuteMsg::EnforceRetention => customer_data::enforce_retention::<S, T>(storage, clock),
+        ExecuteMsg::MigrateAll => self::migrate_records::<S, T>(storage, clock),
+        ExecuteMsg::ImportNewspapers { csv, mode } => {
+            import::import::<S, T>(storage, clock, &csv, mode)
+        }
+        ExecuteMsg::Restore { chunk } => {
+            snapshot::restore::<S, T>(storage, clock, chunk).and_then(|restored| {
+                emit_event::<S, T>(storage, clock, &Event::snapshot_restored(restored))
+            })
+        }
+        ExecuteMsg::Batch(_) => Err(ServiceError::NestedBatch),
+    }
+}
+
+pub(crate) fn query<S, T>(msg: QueryMsg, clock: &T) -> StdResult<ByteArray, ByteArray>
 where
     S: Storage + Default,
-    T: Time + Default,
+    T: Time,
 {
-    self::define_end_year::<S, T>(signature.as_str(), final_year).map_err(|error| error.serialize())
+    let format = self::date_format(&S::default());
+    match msg {
+        QueryMsg::NewspapersByDate { date } => self::newspapers_by_date::<S, T>(date, clock),
+        QueryMsg::NewspapersInRange { from, to } => {
+            self::newspapers_in_range::<S, T>(from, to, format, clock)
+        }
+        QueryMsg::AnniversaryIssues { date, years } => {
+            self::anniversary_issues::<S, T>(date, years, format, clock)
+        }
+        QueryMsg::NearestIssues {
+            signature,
+            date,
+            window_days,
+        } => self::nearest_issues::<S, T>(signature.as_str(), date, window_days, format, clock),
+        QueryMsg::ExportCustomerData { phone } => self::export_customer_data::<S>(phone, format),
+        QueryMsg::CheckIntegrity => self::check_integrity::<S, T>(clock),
+        QueryMsg::AuditLog { from_seq, limit } => self::audit_log::<S>(from_seq, limit),
+        QueryMsg::ExportCatalog { format, cursor } => self::export_catalog::<S>(format, cursor),
+        QueryMsg::Snapshot { cursor } => self::snapshot::<S>(cursor),
+        QueryMsg::Schema => self::schema::<S>(),
+    }
+}
+
+/// The format of the dates in the responses and the events, the default one if the setting
+/// cannot be read, which the integrity check reports.
+fn date_format<S>(storage: &S) -> DateFormat
+where
+    S: Storage,
+{
+    ConfigRepo::load::<_, DateFormat>(storage)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
 }
 
-pub(crate) fn specify_max_cards<S>(max_number: u8) -> StdResult<Vec<bindings::Event>, ByteArray>
+fn export_customer_data<S>(
+    phone: PhoneNumber,
+    format: DateFormat,
+) -> StdResult<ByteArray, ByteArray>
 where
     S: Storage + Default,
 {
-    self::configure_max_cards::<S>(max_number).map_err(|error| error.serialize())
+    format
+        .to_vec(&customer_data::export(&S::default(), &phone))
+        .map_err(|error| ServiceError::SerializationFault(error).serialize())
 }
 
-pub(crate) fn create_order<S, T>(
-    order: OrderRequest<S, T>,
-) -> StdResult<Vec<bindings::Event>, ByteArray>
+fn check_integrity<S, T>(clock: &T) -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    serde_json::to_vec(&integrity::check(&S::default(), clock.now()))
+        .map_err(|error| ServiceError::SerializationFault(error).serialize())
+}
+
+fn audit_log<S>(from_seq: u64, limit: u32) -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+{
+    audit::log(&S::default(), from_seq, limit)
+        .and_then(|log| serde_json::to_vec(&log).map_err(ServiceError::SerializationFault))
+        .map_err(|error| error.serialize())
+}
+
+fn export_catalog<S>(
+    format: CatalogFormat,
+    cursor: Option<Signature>,
+) -> StdResult<ByteArray, ByteArray>
 where
     S: Storage + Default,
-    T: Time + Default,
 {
-    self::place_order(order).map_err(|error| error.serialize())
+    catalog::export(&S::default(), format, cursor.as_ref())
+        .and_then(|page| serde_json::to_vec(&page).map_err(ServiceError::SerializationFault))
+        .map_err(|error| error.serialize())
 }
 
-pub(crate) fn newspapers_by_date<S, T>(date: Date) -> StdResult<ByteArray, ByteArray>
+fn snapshot<S>(cursor: Option<String>) -> StdResult<ByteArray, ByteArray>
 where
     S: Storage + Default,
-    T: Time + Default,
 {
-    newspaper::newspapers_by_date::<S, T>(date)
+    snapshot::snapshot(&S::default(), cursor.as_deref())
+        .and_then(|chunk| serde_json::to_vec(&chunk).map_err(ServiceError::SerializationFault))
+        .map_err(|error| error.serialize())
+}
+
+fn schema<S>() -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+{
+    serde_json::to_vec(&schema::contract::<S>())
+        .map_err(|error| ServiceError::SerializationFault(error).serialize())
+}
+
+fn newspapers_by_date<S, T>(date: Date, clock: &T) -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    newspaper::newspapers_by_date::<S, T>(date, clock)
         .map_err(|error| ServiceError::DomainError(error).serialize())
 }
 
-// TODO! - do we need 'newspaper' to pe present in every name
-fn new_newspaper<S>(newspaper: Newspaper) -> StdResult<Vec<bindings::Event>, ServiceError>
+fn newspapers_in_range<S, T>(
+    from: Date,
+    to: Date,
+    format: DateFormat,
+    clock: &T,
+) -> StdResult<ByteArray, ByteArray>
 where
     S: Storage + Default,
+    T: Time,
 {
-    let signature = newspaper.identificator();
-    let mut storage = S::default();
-    storage
-        .retrieve(signature)
-        .map(|_| Err(ServiceError::DuplicateSignature))
-        .unwrap_or({
-            persist_and_emit_event(
-                &mut storage,
-                signature,
-                &newspaper,
-                "dnevest_n_n",
-                Event::newspaper_created(signature),
-            )
+    newspaper::newspapers_in_range::<S, T>(from, to, format, clock)
+        .map_err(|error| ServiceError::DomainError(error).serialize())
+}
+
+fn anniversary_issues<S, T>(
+    date: Date,
+    years: Year,
+    format: DateFormat,
+    clock: &T,
+) -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    newspaper::anniversary_issues::<S, T>(date, years, format, clock)
+        .map_err(|error| ServiceError::DomainError(error).serialize())
+}
+
+fn nearest_issues<S, T>(
+    signature: &str,
+    date: Date,
+    window_days: u16,
+    format: DateFormat,
+    clock: &T,
+) -> StdResult<ByteArray, ByteArray>
+where
+    S: Storage + Default,
+    T: Time,
+{
+    NewspaperRepo::find(&S::default(), signature)
+        .map_err(ServiceError::Storage)
+        .and_then(|unchecked| unchecked.ok_or(ServiceError::NotFound("Newspaper not found")))
+        .and_then(|unchecked| {
+            unchecked
+                .into_checked(clock.now())
+                .map_err(ServiceError::DomainError)
+        })
+        .and_then(|newspaper| {
+            newspaper::nearest_issues(&newspaper, date, window_days)
+                .map_err(ServiceError::DomainError)
+        })
+        .and_then(|nearest| {
+            format
+                .to_vec(&nearest)
+                .map_err(ServiceError::SerializationFault)
+        })
+        .map_err(|error| error.serialize())
+}
+
+// TODO! - do we need 'newspaper' to pe present in every name
+fn new_newspaper<S, T>(
+    storage: &mut S,
+    clock: &T,
+    request: NewspaperRequest,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let newspaper = request.check(clock).map_err(ServiceError::DomainError)?;
+    if NewspaperRepo::contains(storage, newspaper.identificator()) {
+        Err(ServiceError::DuplicateSignature)
+    } else {
+        let change = Change::new(
+            storage,
+            message::CREATE_NEWSPAPER,
+            &NEWSPAPERS,
+            newspaper.identificator(),
+        );
+        persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+            NewspaperRepo::save(storage, &newspaper).map(|()| Event::newspaper_created(newspaper))
         })
+    }
 }
 
 fn define_end_year<S, T>(
+    storage: &mut S,
+    clock: &T,
     signature: &str,
     final_year: Year,
 ) -> StdResult<Vec<bindings::Event>, ServiceError>
 where
-    S: Storage + Default,
-    T: Time + Default,
+    S: Storage,
+    T: Time,
 {
-    let mut storage = S::default();
-    storage
-        .retrieve(signature)
-        .ok_or(ServiceError::NotFound("Newspaper not found"))
-        .and_then(|ser_newspaper| {
-            serde_json::from_slice(&ser_newspaper)
-                .map_err(ServiceError::DeserializationFault)
-                .and_then(|newspaper: Newspaper| {
-                    newspaper
-                        .add_end_year(final_year, T::now())
-                        .map_err(ServiceError::DomainError)
-                        .and_then(|newspaper| {
-                            persist_and_emit_event(
-                                &mut storage,
-                                signature,
-                                &newspaper,
-                                "dnevest_end_y",
-                                Event::added_end_year(signature),
-                            )
-                        })
+    NewspaperRepo::find(storage, signature)
+        .map_err(ServiceError::Storage)
+        .and_then(|unchecked| unchecked.ok_or(ServiceError::NotFound("Newspaper not found")))
+        .and_then(|unchecked| {
+            unchecked
+                .into_checked(clock.now())
+                .map_err(ServiceError::DomainError)
+        })
+        .and_then(|newspaper| {
+            let old_end_year = newspaper.end_year();
+            newspaper
+                .add_end_year(final_year, clock.now())
+                .map_err(ServiceError::DomainError)
+                .and_then(|newspaper| {
+                    let change =
+                        Change::new(storage, message::ADD_FINAL_YEAR, &NEWSPAPERS, signature);
+                    persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+                        NewspaperRepo::save(storage, &newspaper)
+                            .map(|()| Event::added_end_year(signature, old_end_year, final_year))
+                    })
                 })
         })
 }
 
-fn configure_max_cards<S>(max_number: u8) -> StdResult<Vec<bindings::Event>, ServiceError>
+fn configure_max_cards<S, T>(
+    storage: &mut S,
+    clock: &T,
+    max_number: u8,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
 where
-    S: Storage + Default,
+    S: Storage,
+    T: Time,
 {
-    let mut storage = S::default();
-    let key = "max_cards";
-    let needs_update = storage
-        .retrieve(key)
-        .and_then(|ser| {
-            serde_json::from_slice::<MaxCards>(&ser)
-                .map_err(ServiceError::DeserializationFault)
-                .ok()
-        })
-        .map_or(true, |max_cards| max_cards.number() != max_number);
+    let needs_update = ConfigRepo::load::<_, MaxCards>(storage)
+        .ok()
+        .flatten()
+        .is_none_or(|max_cards| max_cards.number() != max_number);
 
     if needs_update {
-        persist_and_emit_event(
-            &mut storage,
-            key,
-            &MaxCards::new(max_number),
-            "dnevest_max_card",
-            Event::specified_max_cards(key),
-        )
+        let change = Change::new(storage, message::SPECIFY_MAX_CARDS, &CONFIG, MaxCards::NAME);
+        persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+            ConfigRepo::save(storage, &MaxCards::new(max_number))
+                .map(|()| Event::specified_max_cards(max_number))
+        })
     } else {
         Ok(Vec::new())
     }
 }
 
-fn place_order<S, T>(order: OrderRequest<S, T>) -> StdResult<Vec<bindings::Event>, ServiceError>
+fn configure_delivery_schedule<S, T>(
+    storage: &mut S,
+    clock: &T,
+    schedule: DeliverySchedule,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
 where
-    S: Storage + Default,
-    T: Time + Default,
+    S: Storage,
+    T: Time,
+{
+    let change = Change::new(
+        storage,
+        message::SPECIFY_DELIVERY_SCHEDULE,
+        &CONFIG,
+        DeliverySchedule::NAME,
+    );
+    persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+        ConfigRepo::save(storage, &schedule).map(|()| Event::specified_delivery_schedule(schedule))
+    })
+}
+
+fn configure_price_list<S, T>(
+    storage: &mut S,
+    clock: &T,
+    prices: PriceList,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let change = Change::new(
+        storage,
+        message::SPECIFY_PRICE_LIST,
+        &CONFIG,
+        PriceList::NAME,
+    );
+    persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+        ConfigRepo::save(storage, &prices).map(|()| Event::specified_price_list(prices))
+    })
+}
+
+fn configure_retention<S, T>(
+    storage: &mut S,
+    clock: &T,
+    days: u16,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let change = Change::new(
+        storage,
+        message::SPECIFY_RETENTION,
+        &CONFIG,
+        RetentionPeriod::NAME,
+    );
+    persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+        ConfigRepo::save(storage, &RetentionPeriod::new(days))
+            .map(|()| Event::specified_retention(days))
+    })
+}
+
+/// The event, and the rest of a batch, already write the dates in the new format.
+fn configure_date_format<S, T>(
+    storage: &mut S,
+    clock: &T,
+    format: DateFormat,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let change = Change::new(
+        storage,
+        message::SPECIFY_DATE_FORMAT,
+        &CONFIG,
+        DateFormat::NAME,
+    );
+    persist_and_emit_event::<S, T, _>(storage, clock, change, |storage| {
+        ConfigRepo::save(storage, &format).map(|()| Event::specified_date_format(format))
+    })
+}
+
+fn migrate_records<S, T>(
+    storage: &mut S,
+    clock: &T,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let migration = NewspaperRepo::migrate(storage)
+        .add(OrderRepo::migrate(storage))
+        .add(ConfigRepo::migrate(storage))
+        .add(CounterRepo::migrate(storage));
+    emit_event::<S, T>(
+        storage,
+        clock,
+        &Event::records_migrated(migration.migrated, migration.failed),
+    )
+}
+
+fn create_order<S, H, T>(
+    storage: &mut S,
+    clock: &T,
+    order: Fingerprinted<OrderRequest<H>>,
+    idempotency_key: Option<IdempotencyKey>,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    H: Storage + Default,
+    T: Time,
+{
+    match idempotency_key {
+        Some(key) => self::place_order_once::<S, H, T>(storage, clock, order, &key),
+        None => self::checked_order(storage, clock, order.into_inner())
+            .and_then(|order| self::place_order::<S, T>(storage, clock, order)),
+    }
+}
+
+fn checked_order<S, H, T>(
+    storage: &S,
+    clock: &T,
+    order: OrderRequest<H>,
+) -> StdResult<Order, ServiceError>
+where
+    S: Storage,
+    H: Storage + Default,
+    T: Time,
 {
     order
-        .try_into()
+        .check(storage, clock)
         .map_err(ServiceError::InvalidOrder)
-        .and_then(|order: Order| {
-            let key = order.identifier();
-            let key = key.as_str();
-            let mut storage = S::default();
-            storage
-                .retrieve(key)
-                .map(|_| Err(ServiceError::DuplicateOrder))
-                .unwrap_or({
-                    persist_and_emit_event(
-                        &mut storage,
-                        key,
-                        &order,
-                        "dnevest_n_o",
-                        Event::saved_order(key),
-                    )
+}
+
+fn place_order<S, T>(
+    storage: &mut S,
+    clock: &T,
+    order: Order,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    self::save_order::<S, T>(storage, clock, order)
+        .and_then(|(_, event)| emit_event::<S, T>(storage, clock, &event))
+}
+
+/// Places the order only the first time it is requested with this idempotency key.
+/// Every retry before the key expires returns the events of the first request, without
+/// checking the order again, since it may no longer pass, e.g. after the Express cut-off.
+fn place_order_once<S, H, T>(
+    storage: &mut S,
+    clock: &T,
+    request: Fingerprinted<OrderRequest<H>>,
+    idempotency_key: &IdempotencyKey,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    H: Storage + Default,
+    T: Time,
+{
+    let now = clock.now();
+    Replay::find(storage, idempotency_key, now.timestamp).and_then(|replay| match replay {
+        Some(replay) => replay.events(request.fingerprint()),
+        None => {
+            let fingerprint = request.fingerprint().to_string();
+            self::checked_order(storage, clock, request.into_inner())
+                .and_then(|order| self::save_order::<S, T>(storage, clock, order))
+                .and_then(|(_, event)| {
+                    event
+                        .publish(now, self::date_format(storage))
+                        .and_then(|event| {
+                            Replay::save(
+                                storage,
+                                idempotency_key,
+                                &fingerprint,
+                                &event,
+                                now.timestamp,
+                            )
+                            .map(|()| vec![event])
+                        })
                 })
+        }
+    })
+}
+
+/// Persists the order along with an index from its public number to its key,
+/// and returns the key and the event announcing the public number.
+/// The same customer cannot place two orders at the same millisecond.
+fn save_order<S, T>(
+    storage: &mut S,
+    clock: &T,
+    order: Order,
+) -> StdResult<(String, Event), ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    if OrderRepo::placed_at(storage, order.created_on_ms())
+        .into_iter()
+        .flatten()
+        .any(|placed| placed.phone() == order.phone())
+    {
+        return Err(ServiceError::DuplicateOrder);
+    }
+    next_order_number::<S, T>(storage, clock, order.year()).and_then(|number| {
+        let key = OrderRepo::key(&order, &number);
+        let order = order.with_number(number.clone());
+        let change = Change::order(storage, message::CREATE_ORDER, &key, Some(&number));
+        OrderRepo::index_number(storage, &number, &key)
+            .and_then(|()| OrderRepo::save(storage, &key, &order))
+            .map_err(ServiceError::Storage)
+            .and_then(|()| audit::record::<S, T>(storage, clock, change))
+            .map(|()| (key, Event::saved_order(order.summary())))
+    })
+}
+
+/// Increments the persisted order counter and derives the public number from it.
+/// A number already in the index, e.g. when the counter was restored from an older
+/// snapshot, is skipped rather than reused.
+fn next_order_number<S, T>(
+    storage: &mut S,
+    clock: &T,
+    year: Year,
+) -> StdResult<OrderNumber, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    let key = number_key::<S, T>(storage, clock)?;
+    loop {
+        let sequence = CounterRepo::next(storage, CounterRepo::ORDERS)?;
+        let number =
+            OrderNumber::new(year, sequence, &key).ok_or(ServiceError::OrderNumbersExhausted)?;
+        if OrderRepo::id_by_number(storage, &number)?.is_none() {
+            return Ok(number);
+        }
+    }
+}
+
+/// The key scrambling the order numbers, created along with the first of them.
+fn number_key<S, T>(storage: &mut S, clock: &T) -> StdResult<NumberKey, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    ConfigRepo::load::<_, NumberKey>(storage)
+        .and_then(|key| match key {
+            Some(key) => Ok(key),
+            None => {
+                let key = NumberKey::from_time(clock.now().timestamp);
+                ConfigRepo::save(storage, &key).map(|()| key)
+            }
         })
+        .map_err(ServiceError::Storage)
 }
 
-fn persist_and_emit_event<S, I>(
+/// Persists the change, records it in the audit log and emits the event announcing it.
+/// The event is emitted only if the change has been persisted.
+fn persist_and_emit_event<S, T, F>(
     storage: &mut S,
-    key: &str,
-    item: &I,
-    event_id: &str,
-    event: Event,
+    clock: &T,
+    change: Change<'_>,
+    persist: F,
 ) -> StdResult<Vec<bindings::Event>, ServiceError>
 where
     S: Storage,
-    I: Serialize,
+    T: Time,
+    F: FnOnce(&mut S) -> repo::Result<Event>,
 {
-    serde_json::to_vec(item)
-        .map_err(ServiceError::SerializationFault)
-        .and_then(|serialized| {
-            storage.persist(key, &serialized);
-            event.serialize().map(|serialized_event| {
-                vec![bindings::Event {
-                    id: event_id.to_string(),
-                    content: serialized_event,
-                }]
-            })
+    persist(storage)
+        .map_err(ServiceError::Storage)
+        .and_then(|event| {
+            audit::record::<S, T>(storage, clock, change)
+                .and_then(|()| emit_event::<S, T>(storage, clock, &event))
         })
 }
 
+/// The event writes its dates in the format configured in the storage, including a change
+/// of the format earlier in the same batch.
+fn emit_event<S, T>(
+    storage: &S,
+    clock: &T,
+    event: &Event,
+) -> StdResult<Vec<bindings::Event>, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    event
+        .publish(clock.now(), self::date_format(storage))
+        .map(|event| vec![event])
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         bindings,
-        newspaper::Newspaper,
+        msgs::ExecuteMsg,
+        newspaper::{Date, DateFormat, Newspaper},
+        order::{Order, OrderRequest},
+        repo::{CounterRepo, NewspaperRepo, OrderRepo, COUNTERS, NEWSPAPERS},
         response::Event,
-        services::{MockHost, ServiceError},
+        services::{Fingerprinted, IdempotencyKey, MockClock, MockHost, ServiceError},
+        Time,
     };
 
-    use super::OrderRequest;
+    use super::{message, Change};
+
+    const NEWSPAPER: &str = r#"{"signature":"В1905","name":"Поглед","start_year":1966,"end_year":1996,"weekly_schedule":[true,false,false,false,false,false,false]}"#;
+    const ORDER: &str = r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
 
     #[test]
     fn create_newspaper() {
-        let newspaper = newspaper();
+        let request = serde_json::from_str(NEWSPAPER).expect("failed to deserialize JSON");
 
-        let res = super::create_newspaper::<MockHost>(newspaper);
+        let res = super::new_newspaper::<_, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            request,
+        );
         assert_eq!((res.unwrap())[0].id, "dnevest_n_n".to_string());
     }
 
     #[test]
     fn newspaper_not_found() {
-        let res = super::define_end_year::<MockHost, MockHost>("В1223", 2021);
+        let res = super::define_end_year::<_, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            "В1223",
+            2021,
+        );
         assert_err(res, "Newspaper not found");
     }
 
+    #[test]
+    fn nearest_issues_of_unknown_newspaper() {
+        let res = super::nearest_issues::<MockHost, MockClock>(
+            "В1223",
+            Date::new(13, 8, 2024),
+            7,
+            DateFormat::default(),
+            &MockClock::default(),
+        )
+        .expect_err("expected the newspaper to be unknown");
+        assert_eq!(
+            serde_json::from_slice::<String>(&res).unwrap(),
+            "Newspaper not found"
+        );
+    }
+
     #[test]
     fn add_max_cards() {
-        let res = super::configure_max_cards::<MockHost>(30);
+        let res = super::configure_max_cards::<_, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            30,
+        );
         assert_eq!(res.unwrap()[0].id, "dnevest_max_card");
     }
 
+    #[test]
+    fn add_delivery_schedule() {
+        let json = r#"{"cutoff_hour":12,"cutoff_minute":0,"utc_offset_minutes":120,"standard_days":3,"express_days":1}"#;
+        let schedule = serde_json::from_str(json).expect("failed to deserialize JSON");
+        let res = super::configure_delivery_schedule::<_, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            schedule,
+        );
+        assert_eq!(res.unwrap()[0].id, "dnevest_dlv_sched");
+    }
+
+    #[test]
+    fn date_format() {
+        let mut storage = MockHost::default();
+        let res = super::configure_date_format::<_, MockClock>(
+            &mut storage,
+            &MockClock::default(),
+            DateFormat::Iso,
+        );
+        assert_eq!(res.unwrap()[0].id, "dnevest_date_fmt");
+
+        let events =
+            super::place_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+                .unwrap();
+        assert!(String::from_utf8(events[0].content.clone())
+            .unwrap()
+            .contains(r#""estimate":{"ship_on":"2024-11-29","deliver_on":"2024-12-04"}"#));
+
+        // the stored order keeps its dates in the default format
+        assert!(OrderRepo::all(&storage)
+            .into_iter()
+            .all(|order| serde_json::to_string(&order.unwrap())
+                .unwrap()
+                .contains(r#""ship_on":"29-11-2024""#)));
+        assert_eq!(
+            super::date_format(&MockHost::default()),
+            DateFormat::default()
+        );
+    }
+
     #[test]
     fn create_order() {
-        let res = super::create_order(order());
+        let res = apply_order(fingerprinted(ORDER));
+        assert_eq!(res.unwrap()[0].id, "dnevest_n_o");
+    }
+
+    #[test]
+    fn order_number() {
+        let mut storage = MockHost::default();
+        let (key, event) =
+            super::save_order::<_, MockClock>(&mut storage, &MockClock::default(), order())
+                .unwrap();
+
+        let number = MockHost::order_number(2024, 1);
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            format!(
+                r#"{{"SavedOrder":{{"number":"DN-2024-242129-3","cards":1,"priority":"Standart","estimate":{{"ship_on":"29-11-2024","deliver_on":"04-12-2024"}},"created_on_ms":1732880395000,"price":{{"cards_cents":500,"discount_cents":0,"delivery_cents":400,"total_cents":900}}}}}}"#
+            )
+        );
+        assert_eq!(
+            OrderRepo::id_by_number(&storage, &number).unwrap(),
+            Some(key)
+        );
+    }
+
+    #[test]
+    fn monotonic_order_numbers() {
+        let mut storage = MockHost::default();
+        let first =
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024)
+                .unwrap();
+        let second =
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024)
+                .unwrap();
+
+        assert_eq!(first, MockHost::order_number(2024, 1));
+        assert_eq!(second, MockHost::order_number(2024, 2));
+    }
+
+    #[test]
+    fn taken_order_number() {
+        let mut storage = MockHost::default();
+        let first =
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024)
+                .unwrap();
+        OrderRepo::index_number(&mut storage, &first, "1732880395000_first").unwrap();
+        // e.g. the counter is restored from an older snapshot
+        COUNTERS
+            .save(&mut storage, CounterRepo::ORDERS, &0u64)
+            .unwrap();
+
+        let next =
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024)
+                .unwrap();
+        assert_eq!(next, MockHost::order_number(2024, 2));
+    }
+
+    #[test]
+    fn exhausted_order_numbers() {
+        let mut storage = MockHost::default();
+        COUNTERS
+            .save(&mut storage, CounterRepo::ORDERS, &999_998u64)
+            .unwrap();
+
+        let last =
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024)
+                .unwrap();
+        assert_eq!(last, MockHost::order_number(2024, 999_999));
+        assert!(matches!(
+            super::next_order_number::<_, MockClock>(&mut storage, &MockClock::default(), 2024),
+            Err(ServiceError::OrderNumbersExhausted)
+        ));
+    }
+
+    #[test]
+    fn replay_order() {
+        let mut storage = MockHost::default();
+        let key = IdempotencyKey::try_from("3f2b9c4e".to_string()).unwrap();
+        let now = MockClock::default().now().timestamp;
+
+        let first = place_once(&mut storage, ORDER, &key, now).unwrap();
+        // the template is no longer allowed, but the retry is not checked again
+        storage = storage.with_max_cards(5);
+        // the same order, written differently
+        let retry = ORDER.replacen(r#"{"wish_card""#, r#"{ "wish_card""#, 1);
+        let replayed = place_once(&mut storage, &retry, &key, now + 1).unwrap();
+        assert_eq!(first[0].id, replayed[0].id);
+        assert_eq!(first[0].content, replayed[0].content);
+
+        assert_err(
+            super::place_order::<_, MockClock>(&mut storage, &MockClock::default(), order()),
+            "Cannot create the order because it already exists",
+        );
+    }
+
+    #[test]
+    fn reused_idempotency_key() {
+        let mut storage = MockHost::default();
+        let key = IdempotencyKey::try_from("3f2b9c4e".to_string()).unwrap();
+        let now = MockClock::default().now().timestamp;
+
+        place_once(&mut storage, ORDER, &key, now).unwrap();
+        let other = ORDER.replace("Пловдив", "София");
+        assert_err(
+            place_once(&mut storage, &other, &key, now + 1),
+            "The idempotency key was already used for another request",
+        );
+        assert_eq!(OrderRepo::all(&storage).len(), 1);
+    }
+
+    #[test]
+    fn create_order_with_items() {
+        let json = r#"{"items":[{"wish_card":{"covers":{"preference":"В1616","options":[null,null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден, Мария!","font_type":"Arial","font_size":12,"template_id":10},"quantity":1},{"wish_card":{"covers":{"preference":"В1612","options":["В4667",null]},"background":[0,0,255],"frame":"Black","message":"Честит рожден ден, Иван!","font_type":"Arial","font_size":12,"template_id":3},"quantity":2}],"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
+        let order = serde_json::from_str(json).expect("failed to deserialize JSON");
+        let res = apply_order(order);
         assert_eq!(res.unwrap()[0].id, "dnevest_n_o");
     }
 
+    #[test]
+    fn create_order_with_card_and_items() {
+        let json = r#"{"wish_card":{"covers":{"preference":"В1616","options":[null,null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Arial","font_size":12,"template_id":10},"items":[{"wish_card":{"covers":{"preference":"В1612","options":[null,null]},"background":[0,0,255],"frame":"Black","message":"Честит рожден ден!","font_type":"Arial","font_size":12,"template_id":3},"quantity":2}],"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
+        let order = serde_json::from_str(json).expect("failed to deserialize JSON");
+        assert_err(apply_order(order), "either a wish card or items");
+    }
+
     #[test]
     fn persist_and_emit_event() {
         let mut storage = MockHost::default();
@@ -243,40 +899,99 @@ mod tests {
             [true, true, true, true, true, true, true],
         );
         let signature = newspaper.identificator();
-        let event_id = "dnevest_end_y";
-        let event = super::persist_and_emit_event(
+        let change = Change::new(&storage, message::ADD_FINAL_YEAR, &NEWSPAPERS, signature);
+        let event = super::persist_and_emit_event::<_, MockClock, _>(
             &mut storage,
-            signature,
-            &newspaper,
-            event_id,
-            Event::added_end_year(signature),
+            &MockClock::default(),
+            change,
+            |storage| {
+                NewspaperRepo::save(storage, &newspaper)
+                    .map(|()| Event::added_end_year(signature, None, 2024))
+            },
         )
         .unwrap();
 
-        assert_eq!(event[0].id, event_id.to_string());
+        assert_eq!(event[0].id, "dnevest_end_y".to_string());
         assert_eq!(
             event[0].content,
-            Event::added_end_year(signature)
-                .serialize()
+            Event::added_end_year(signature, None, 2024)
+                .serialize(MockClock::default().now(), DateFormat::default())
                 .expect("serialization failed")
+        );
+
+        let log = serde_json::to_value(super::audit::log(&storage, 1, 10).unwrap()).unwrap();
+        assert_eq!(log[0]["message"], "AddFinalYear");
+        assert_eq!(log[0]["record"], "newspaper/В1612");
+        assert_ne!(log[0]["before"], log[0]["after"]);
+    }
+
+    #[test]
+    fn migrate_all() {
+        let newspaper = r#"{"signature":"В1905","name":"Поглед","start_year":1966,"end_year":1996,"weekly_schedule":[true,false,false,false,false,false,false]}"#;
+        let order = r#"{"wish_card":{"covers":{"preference":"В1616","options":[null,null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Arial","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"},"created_on_ms":1732880395000}"#;
+        let mut storage = MockHost::default()
+            .with_record("В1905", newspaper)
+            .with_record("1732880395000_0873528495", order)
+            .with_record("order_counter", "7");
+
+        let res =
+            super::migrate_records::<_, MockClock>(&mut storage, &MockClock::default()).unwrap();
+        assert_eq!(
+            res[0].content,
+            Event::records_migrated(3, 0)
+                .serialize(MockClock::default().now(), DateFormat::default())
+                .unwrap()
+        );
+        assert!(NewspaperRepo::contains(&storage, "В1905"));
+        assert_eq!(OrderRepo::all(&storage).len(), 1);
+        assert!(OrderRepo::all(&storage)[0].is_ok());
+
+        let res =
+            super::migrate_records::<_, MockClock>(&mut storage, &MockClock::default()).unwrap();
+        assert_eq!(
+            res[0].content,
+            Event::records_migrated(0, 0)
+                .serialize(MockClock::default().now(), DateFormat::default())
+                .unwrap()
+        );
+    }
+
+    fn order() -> Order {
+        fingerprinted(ORDER)
+            .into_inner()
+            .check(&MockHost::default(), &MockClock::default())
+            .expect("the order is valid")
+    }
+
+    fn place_once(
+        storage: &mut MockHost,
+        order: &str,
+        key: &IdempotencyKey,
+        now: u64,
+    ) -> Result<Vec<bindings::Event>, ServiceError> {
+        super::place_order_once::<_, MockHost, MockClock>(
+            storage,
+            &MockClock::at(now),
+            fingerprinted(order),
+            key,
         )
     }
 
-    fn newspaper() -> Newspaper {
-        Newspaper::new_unchecked(
-            "В1905",
-            "Поглед",
-            1966,
-            Some(1996),
-            [true, false, false, false, false, false, false],
+    fn apply_order(
+        order: Fingerprinted<OrderRequest<MockHost>>,
+    ) -> Result<Vec<bindings::Event>, ServiceError> {
+        super::apply::<_, MockHost, MockClock>(
+            &mut MockHost::default(),
+            &MockClock::default(),
+            ExecuteMsg::CreateOrder {
+                order,
+                idempotency_key: None,
+            },
         )
     }
 
-    fn order() -> OrderRequest<MockHost, MockHost> {
-        let json = r#"{"wish_card":{"covers":{"preference":"В1616","options":["В4667",null]},"background":[255,0,0],"frame":"White","message":"Честит рожден ден!","font_type":"Times New Roman","font_size":12,"template_id":10},"delivery":{"customer_names":"Тодор Георгиев","phone_number":"0873528495","address":"Пловдив, ул.Тракия 12","priority":"Standart"}}"#;
-        let unchecked: OrderRequest<MockHost, MockHost> =
-            serde_json::from_str(json).expect("failed to deserialize JSON");
-        unchecked
+    fn fingerprinted(json: &str) -> Fingerprinted<OrderRequest<MockHost>> {
+        serde_json::from_str(json).expect("failed to deserialize JSON")
     }
 
     fn assert_err(r: Result<Vec<bindings::Event>, ServiceError>, msg: &str) {
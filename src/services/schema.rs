@@ -0,0 +1,155 @@
+use schemars::{generate::SchemaSettings, JsonSchema, Schema};
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+
+use crate::{
+    msgs::{Envelope, ExecuteMsg, QueryMsg, Response},
+    newspaper::{AnniversaryDTO, NearestIssuesDTO, NewspaperIssuesDTO, QueryNewspaperDTO},
+    response::Published,
+    Storage,
+};
+
+use super::{
+    audit::AuditEntry, catalog::CatalogPage, customer_data::CustomerData,
+    integrity::IntegrityReport, snapshot::Chunk,
+};
+
+/// The JSON Schema of the messages, of the published events and of the responses to
+/// the queries, by the name of the query, along with the envelopes naming the version
+/// of a message and of the response to it. The messages are described as they are read,
+/// the events and the responses as they are written.
+#[derive(Serialize)]
+pub(crate) struct Contract {
+    execute: Schema,
+    query: Schema,
+    event: Schema,
+    responses: BTreeMap<&'static str, Schema>,
+    envelope: Schema,
+    response_envelope: Schema,
+}
+
+/// The schema itself is left out of the responses, since it describes only the others.
+pub(super) fn contract<S>() -> Contract
+where
+    S: Storage + Default,
+{
+    Contract {
+        execute: request::<ExecuteMsg<S>>(),
+        query: request::<QueryMsg>(),
+        event: response::<Published<'static>>(),
+        responses: BTreeMap::from([
+            ("NewspapersByDate", response::<Vec<QueryNewspaperDTO>>()),
+            ("NewspapersInRange", response::<Vec<NewspaperIssuesDTO>>()),
+            ("AnniversaryIssues", response::<Vec<AnniversaryDTO>>()),
+            ("NearestIssues", response::<NearestIssuesDTO>()),
+            ("ExportCustomerData", response::<CustomerData>()),
+            ("CheckIntegrity", response::<IntegrityReport>()),
+            ("AuditLog", response::<Vec<AuditEntry>>()),
+            ("ExportCatalog", response::<CatalogPage>()),
+            ("Snapshot", response::<Chunk>()),
+        ]),
+        envelope: request::<Envelope<'static>>(),
+        response_envelope: response::<Response<'static>>(),
+    }
+}
+
+fn request<M>() -> Schema
+where
+    M: JsonSchema,
+{
+    SchemaSettings::draft2020_12()
+        .for_deserialize()
+        .into_generator()
+        .into_root_schema_for::<M>()
+}
+
+fn response<M>() -> Schema
+where
+    M: JsonSchema,
+{
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<M>()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::services::MockHost;
+
+    #[test]
+    fn response_of_every_query() {
+        let contract = serde_json::to_value(super::contract::<MockHost>()).unwrap();
+        let queries: Vec<&str> = variants(&contract["query"])
+            .into_iter()
+            .filter(|query| *query != "Schema")
+            .collect();
+        let responses: Vec<&str> = contract["responses"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+
+        assert_eq!(queries.len(), responses.len());
+        assert!(queries.iter().all(|query| responses.contains(query)));
+    }
+
+    #[test]
+    fn messages() {
+        let contract = serde_json::to_value(super::contract::<MockHost>()).unwrap();
+        let execute = &contract["execute"];
+
+        assert!(variants(execute).contains(&"SpecifyMaxCards"));
+        assert_eq!(
+            execute["$defs"]["Signature"]["pattern"],
+            json!("^В(?!0000)[0-9]{4}$")
+        );
+        assert_eq!(
+            execute["$defs"]["NewspaperRequest"]["required"],
+            json!(["signature", "name", "start_year", "weekly_schedule"])
+        );
+        assert_eq!(
+            execute["$defs"]["OrderRequest"]["required"],
+            json!(["delivery"])
+        );
+        assert!(contract["query"]["$defs"]["Date"]["anyOf"][0]["pattern"].is_string());
+        assert_eq!(
+            contract["event"]["required"],
+            json!(["version", "timestamp", "event"])
+        );
+        assert!(contract["event"]["properties"]["deprecation"].is_object());
+    }
+
+    #[test]
+    fn envelopes() {
+        let contract = serde_json::to_value(super::contract::<MockHost>()).unwrap();
+
+        assert_eq!(contract["envelope"]["required"], json!(["v", "msg"]));
+        assert_eq!(contract["envelope"]["additionalProperties"], json!(false));
+        assert_eq!(
+            contract["response_envelope"]["required"],
+            json!(["v", "response"])
+        );
+        assert!(contract["response_envelope"]["properties"]["deprecation"].is_object());
+    }
+
+    /// The names of the variants of an externally tagged enum.
+    fn variants(schema: &Value) -> Vec<&str> {
+        schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|variant| {
+                variant["required"]
+                    .as_array()
+                    .or(variant["enum"].as_array())
+                    .map_or_else(|| vec![&variant["const"]], |names| names.iter().collect())
+            })
+            .filter_map(Value::as_str)
+            .collect()
+    }
+}
@@ -0,0 +1,656 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use std::{iter, result::Result as StdResult};
+
+use crate::{
+    bindings::ByteArray,
+    newspaper::{DateFormat, UncheckedNewspaper},
+    order::{DeliverySchedule, MaxCards, NumberKey, Order, PriceList, RetentionPeriod},
+    repo::{
+        self, Config, CounterRepo, Namespace, OrderRepo, Record, AUDIT, CONFIG, COUNTERS,
+        NEWSPAPERS, ORDERS, ORDER_NUMBERS,
+    },
+    Storage, Time,
+};
+
+use super::{
+    audit::{self, message, AuditEntry, Change},
+    ServiceError,
+};
+
+/// The version of the snapshot format, increased on every incompatible change.
+const SNAPSHOT_VERSION: u32 = 1;
+/// How many records a single chunk holds at most.
+const CHUNK_SIZE: u16 = 100;
+
+/// Tells whether a record of the namespace with the given id can be read, and is the one
+/// stored under that id.
+type Check = fn(&str, &[u8]) -> StdResult<(), ServiceError>;
+
+/// The namespaces in a snapshot. The idempotency keys are left out, since they expire
+/// within a day and their ids are not stored along with them.
+const NAMESPACES: [(&Namespace, Check); 6] = [
+    (&NEWSPAPERS, newspaper),
+    (&ORDERS, order),
+    (&ORDER_NUMBERS, order_number),
+    (&CONFIG, config),
+    (&COUNTERS, counter),
+    (&AUDIT, audit_entry),
+];
+
+const COUNTER_NAMES: &[&str] = &[CounterRepo::ORDERS, CounterRepo::AUDIT];
+
+/// How the ids of the records of a namespace are found, since the range of the storage
+/// holds only the values.
+enum Ids<S> {
+    /// The records are stored under a few known names.
+    Named(&'static [&'static str]),
+    /// The id is read from the record, it is missing if the record cannot be read.
+    Read(fn(&S, &[u8]) -> Option<String>),
+}
+
+/// A record as it is stored, so that it is restored byte for byte, in any version.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Entry {
+    namespace: String,
+    id: String,
+    record: String,
+}
+
+/// A part of the snapshot, ordered by storage key. The next chunk starts from `next_cursor`,
+/// which is missing on the last chunk. The checksum covers the entries.
+/// The records that cannot be read are left out, since their ids cannot be found,
+/// and `skipped` counts them in the chunk whose range of storage keys holds them.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub(crate) struct Chunk {
+    version: u32,
+    entries: Vec<Entry>,
+    checksum: String,
+    #[serde(default)]
+    skipped: u32,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+/// Dumps the records with storage keys from the cursor on. Every namespace is read in
+/// windows of its ids, see `windows`, only until the chunk is full.
+pub(super) fn snapshot<S>(storage: &S, cursor: Option<&str>) -> StdResult<Chunk, ServiceError>
+where
+    S: Storage,
+{
+    let cursor = cursor.unwrap_or_default();
+    let mut namespaces = self::namespaces::<S>();
+    namespaces.sort_by_key(|(namespace, _)| namespace.name());
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for (namespace, ids) in namespaces {
+        // one more than the chunk holds, which starts the next chunk
+        let room = (usize::from(CHUNK_SIZE) + 1).saturating_sub(entries.len());
+        if room == 0 {
+            break;
+        }
+        let prefix = namespace.key("");
+        let from = match cursor.strip_prefix(prefix.as_str()) {
+            Some(from) => from,
+            None if cursor < prefix.as_str() => "",
+            None => continue,
+        };
+        let (listed, unreadable) = self::listed(storage, namespace, &ids, from, room);
+        entries.extend(listed);
+        skipped += unreadable;
+    }
+    let next_cursor = entries.get(usize::from(CHUNK_SIZE)).map(key);
+    entries.truncate(usize::from(CHUNK_SIZE));
+
+    checksum(&entries).map(|checksum| Chunk {
+        version: SNAPSHOT_VERSION,
+        entries,
+        checksum,
+        skipped,
+        next_cursor,
+    })
+}
+
+/// Persists every record of the chunk, or none of them if the chunk is corrupted,
+/// and records each one in the audit log, apart from the log itself and the counters.
+/// The storage cannot delete records, so the ones missing from the snapshot are kept.
+/// Returns the number of restored records.
+pub(super) fn restore<S, T>(
+    storage: &mut S,
+    clock: &T,
+    chunk: Chunk,
+) -> StdResult<u32, ServiceError>
+where
+    S: Storage,
+    T: Time,
+{
+    if chunk.version != SNAPSHOT_VERSION {
+        return Err(ServiceError::UnsupportedSnapshot(chunk.version));
+    }
+    if checksum(&chunk.entries)? != chunk.checksum {
+        return Err(ServiceError::CorruptedSnapshot);
+    }
+    let count = u16::try_from(chunk.entries.len())
+        .ok()
+        .filter(|count| *count <= CHUNK_SIZE)
+        .ok_or(ServiceError::OversizedChunk(CHUNK_SIZE))?;
+    let restored = chunk
+        .entries
+        .iter()
+        .map(|entry| {
+            NAMESPACES
+                .iter()
+                .find(|(namespace, _)| namespace.name() == entry.namespace)
+                .ok_or_else(|| ServiceError::UnknownRecord(key(entry)))
+                .and_then(|(namespace, check)| {
+                    check(&entry.id, entry.record.as_bytes()).map(|()| *namespace)
+                })
+        })
+        .collect::<StdResult<Vec<&Namespace>, ServiceError>>()?;
+
+    restored
+        .into_iter()
+        .zip(&chunk.entries)
+        .try_for_each(|(namespace, entry)| {
+            let change = self::change(storage, namespace, entry);
+            namespace.restore(storage, &entry.id, &entry.record.as_bytes().to_vec());
+            change.map_or(Ok(()), |change| {
+                audit::record::<S, T>(storage, clock, change)
+            })
+        })
+        .map(|()| u32::from(count))
+}
+
+/// The namespaces along with how the ids of their records are found.
+fn namespaces<S>() -> Vec<(&'static Namespace, Ids<S>)>
+where
+    S: Storage,
+{
+    const SETTINGS: &[&str] = &[
+        MaxCards::NAME,
+        DeliverySchedule::NAME,
+        RetentionPeriod::NAME,
+        NumberKey::NAME,
+        DateFormat::NAME,
+        PriceList::NAME,
+    ];
+    vec![
+        (
+            &NEWSPAPERS,
+            Ids::Read(|_, ser| {
+                repo::decode::<UncheckedNewspaper>(ser)
+                    .ok()
+                    .map(|newspaper| newspaper.signature().to_string())
+            }),
+        ),
+        (
+            &ORDERS,
+            Ids::Read(|storage, ser| {
+                repo::decode::<Order>(ser)
+                    .ok()
+                    .and_then(|order| OrderRepo::id(storage, &order).ok())
+            }),
+        ),
+        (
+            &ORDER_NUMBERS,
+            Ids::Read(|storage, ser| {
+                repo::decode::<String>(ser)
+                    .ok()
+                    .and_then(|id| ORDERS.load::<_, Order>(storage, &id).ok().flatten())
+                    .and_then(|order| order.number().map(|number| number.as_str().to_string()))
+            }),
+        ),
+        (&CONFIG, Ids::Named(SETTINGS)),
+        (&COUNTERS, Ids::Named(COUNTER_NAMES)),
+        (
+            &AUDIT,
+            Ids::Read(|_, ser| {
+                repo::decode::<AuditEntry>(ser)
+                    .ok()
+                    .map(|audit_entry| audit_entry.id())
+            }),
+        ),
+    ]
+}
+
+/// The records of the namespace with ids from `from` on, ordered by id, at least `room`
+/// of them if there are as many. Along with them, the number of the records that cannot
+/// be read before the last of the `room` ones, where the next chunk starts.
+fn listed<S>(
+    storage: &S,
+    namespace: &Namespace,
+    ids: &Ids<S>,
+    from: &str,
+    room: usize,
+) -> (Vec<Entry>, u32)
+where
+    S: Storage,
+{
+    match ids {
+        Ids::Named(names) => {
+            let mut entries: Vec<Entry> = names
+                .iter()
+                .filter_map(|name| stored(storage, namespace, name))
+                .filter(|entry| entry.id.as_str() >= from)
+                .collect();
+            entries.sort_by(|a, b| a.id.cmp(&b.id));
+            (entries, 0)
+        }
+        Ids::Read(id) => {
+            let mut entries = Vec::new();
+            let mut unreadable = 0;
+            let mut start = from.to_string();
+            for end in windows(from) {
+                let (listed, skipped) = read(storage, namespace, *id, &start, end.as_deref());
+                entries.extend(listed);
+                if let Some(cut) = entries.get(room - 1).map(|entry| entry.id.clone()) {
+                    let (_, skipped) = read(storage, namespace, *id, &start, Some(&cut));
+                    return (entries, unreadable + skipped);
+                }
+                unreadable += skipped;
+                match end {
+                    Some(end) => start = end,
+                    None => break,
+                }
+            }
+            (entries, unreadable)
+        }
+    }
+}
+
+/// The records of the namespace with ids from `from` up to `to`, ordered by id, along with
+/// the number of the ones that cannot be read.
+fn read<S>(
+    storage: &S,
+    namespace: &Namespace,
+    id: fn(&S, &[u8]) -> Option<String>,
+    from: &str,
+    to: Option<&str>,
+) -> (Vec<Entry>, u32)
+where
+    S: Storage,
+{
+    let (mut entries, unreadable) = namespace.dump_between(storage, from, to).into_iter().fold(
+        (Vec::new(), 0),
+        |(mut entries, unreadable), ser| match id(storage, &ser) {
+            Some(id) => {
+                entries.push(entry(namespace, &id, ser));
+                (entries, unreadable)
+            }
+            None => (entries, unreadable + 1),
+        },
+    );
+    entries.retain(|entry| entry.id.as_str() >= from && to.is_none_or(|to| entry.id.as_str() < to));
+    // a legacy record and its namespaced copy are the same record, the namespaced one comes first
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    entries.dedup_by(|a, b| a.id == b.id);
+    (entries, unreadable)
+}
+
+/// The ends of ever wider windows of the ids from `from` on: the ids starting with `from`,
+/// then with ever shorter prefixes of it, then the rest of the namespace. The storage cannot
+/// limit a range, so a chunk reads the windows one after another until it is full. The ids
+/// share long prefixes, e.g. the time an order was placed, so it reads not many more records
+/// than it holds. A namespace is read whole only by the chunk reaching its start, since there
+/// is no id yet to narrow the windows down from.
+fn windows(from: &str) -> impl Iterator<Item = Option<String>> + '_ {
+    from.char_indices()
+        .map(|(at, c)| at + c.len_utf8())
+        .rev()
+        .filter_map(|len| after_prefix(&from[..len]))
+        .map(Some)
+        .chain(iter::once(None))
+}
+
+/// The first string after all the ones starting with the prefix, missing if there is none.
+fn after_prefix(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        // a letter without a successor is dropped, and the one before it is increased instead
+        if let Some(next) = char::from_u32(u32::from(last) + 1) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// Restoring the log and the counters is bookkeeping, so it is not recorded in the log.
+fn change<'a, S>(storage: &S, namespace: &'a Namespace, entry: &Entry) -> Option<Change<'a>>
+where
+    S: Storage,
+{
+    match namespace.name() {
+        name if name == AUDIT.name() || name == COUNTERS.name() => None,
+        name if name == ORDERS.name() => {
+            let number = repo::decode::<Order>(entry.record.as_bytes())
+                .ok()
+                .and_then(|order| order.number().cloned());
+            Some(Change::order(
+                storage,
+                message::RESTORE,
+                &entry.id,
+                number.as_ref(),
+            ))
+        }
+        _ => Some(Change::new(storage, message::RESTORE, namespace, &entry.id)),
+    }
+}
+
+fn stored<S>(storage: &S, namespace: &Namespace, id: &str) -> Option<Entry>
+where
+    S: Storage,
+{
+    namespace
+        .retrieve(storage, id)
+        .map(|ser| entry(namespace, id, ser))
+}
+
+fn entry(namespace: &Namespace, id: &str, ser: ByteArray) -> Entry {
+    Entry {
+        namespace: namespace.name().to_string(),
+        id: id.to_string(),
+        record: String::from_utf8_lossy(&ser).into_owned(),
+    }
+}
+
+fn key(entry: &Entry) -> String {
+    format!("{}/{}", entry.namespace, entry.id)
+}
+
+fn checksum(entries: &[Entry]) -> StdResult<String, ServiceError> {
+    serde_json::to_vec(entries)
+        .map(|ser| repo::fingerprint(&ser))
+        .map_err(ServiceError::SerializationFault)
+}
+
+fn readable<V>(_id: &str, ser: &[u8]) -> StdResult<(), ServiceError>
+where
+    V: Record,
+{
+    decoded::<V>(ser).map(|_| ())
+}
+
+fn decoded<V>(ser: &[u8]) -> StdResult<V, ServiceError>
+where
+    V: Record,
+{
+    repo::decode::<V>(ser).map_err(ServiceError::Storage)
+}
+
+/// Fails unless the id is one of the ones the record can be stored under.
+fn stored_under<I>(namespace: &Namespace, id: &str, ids: I) -> StdResult<(), ServiceError>
+where
+    I: IntoIterator<Item = String>,
+{
+    if ids.into_iter().any(|own| own == id) {
+        Ok(())
+    } else {
+        Err(ServiceError::MisplacedRecord(namespace.key(id)))
+    }
+}
+
+/// A newspaper is stored under its signature.
+fn newspaper(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    decoded::<UncheckedNewspaper>(ser)
+        .and_then(|newspaper| stored_under(&NEWSPAPERS, id, [newspaper.signature().to_string()]))
+}
+
+/// A numbered order is stored under the time it was placed followed by its number, or by the
+/// phone of the customer until it is erased, as are the orders placed before the numbers.
+fn order(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    decoded::<Order>(ser).and_then(|order| {
+        let numbered = order.number().map(|number| OrderRepo::key(&order, number));
+        let by_phone = (!order.is_anonymized()).then(|| order.identifier());
+        stored_under(&ORDERS, id, numbered.into_iter().chain(by_phone))
+    })
+}
+
+/// A number refers to the key of its order, which holds the number unless it is a legacy one.
+fn order_number(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    decoded::<String>(ser).and_then(|key| match OrderRepo::number_in_key(&key) {
+        Some(number) => stored_under(&ORDER_NUMBERS, id, [number.to_string()]),
+        None => Ok(()),
+    })
+}
+
+fn counter(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    readable::<u64>(id, ser).and_then(|()| {
+        stored_under(
+            &COUNTERS,
+            id,
+            COUNTER_NAMES.iter().map(|name| name.to_string()),
+        )
+    })
+}
+
+/// An entry of the log is stored under its sequence number.
+fn audit_entry(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    decoded::<AuditEntry>(ser).and_then(|audit_entry| stored_under(&AUDIT, id, [audit_entry.id()]))
+}
+
+/// Each setting is stored under its own name.
+fn config(id: &str, ser: &[u8]) -> StdResult<(), ServiceError> {
+    match id {
+        MaxCards::NAME => readable::<MaxCards>(id, ser),
+        DeliverySchedule::NAME => readable::<DeliverySchedule>(id, ser),
+        RetentionPeriod::NAME => readable::<RetentionPeriod>(id, ser),
+        NumberKey::NAME => readable::<NumberKey>(id, ser),
+        DateFormat::NAME => readable::<DateFormat>(id, ser),
+        PriceList::NAME => readable::<PriceList>(id, ser),
+        _ => Err(ServiceError::UnknownRecord(CONFIG.key(id))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        newspaper::Newspaper,
+        repo::{NewspaperRepo, CONFIG},
+        services::{audit, MockClock, MockHost, ServiceError},
+        Storage,
+    };
+
+    use super::Chunk;
+
+    #[test]
+    fn snapshot_and_restore() {
+        // a newspaper stored before the namespaces were introduced
+        let storage = MockHost::default().with_max_cards(30).with_record(
+            "В1111",
+            r#"{"signature":"В1111","name":"Утре","start_year":2020,"end_year":null,"weekly_schedule":[true,true,true,true,true,true,true]}"#,
+        );
+
+        let chunk = super::snapshot(&storage, None).unwrap();
+        assert!(chunk.next_cursor.is_none());
+        let keys: Vec<String> = chunk.entries.iter().map(super::key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "config/max_cards",
+                "newspaper/В1111",
+                "newspaper/В1612",
+                "newspaper/В1616",
+                "newspaper/В4667"
+            ]
+        );
+
+        let mut staging = MockHost::default().with_max_cards(10);
+        let chunk: Chunk = serde_json::from_slice(&serde_json::to_vec(&chunk).unwrap()).unwrap();
+        assert_eq!(
+            super::restore::<_, MockClock>(&mut staging, &MockClock::default(), chunk).unwrap(),
+            5
+        );
+
+        assert_eq!(
+            CONFIG.fingerprint(&staging, "max_cards"),
+            CONFIG.fingerprint(&storage, "max_cards")
+        );
+        assert_eq!(
+            staging.retrieve("newspaper/В1111"),
+            storage.retrieve("В1111")
+        );
+    }
+
+    #[test]
+    fn chunks() {
+        let storage = (1000..1150).fold(MockHost::default(), |storage, number| {
+            let signature = format!("В{}", number);
+            storage.with_newspaper(Newspaper::new_unchecked(
+                &signature,
+                "Вестник",
+                1990,
+                None,
+                [true; 7],
+            ))
+        });
+
+        let first = super::snapshot(&storage, None).unwrap();
+        assert_eq!(first.entries.len(), 100);
+        assert_eq!(first.next_cursor.as_deref(), Some("newspaper/В1099"));
+
+        let second = super::snapshot(&storage, first.next_cursor.as_deref()).unwrap();
+        // the other 51 new newspapers and the 3 of the mock host
+        assert_eq!(second.entries.len(), 54);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn skipped() {
+        let storage = (1000..1150)
+            .fold(MockHost::default(), |storage, number| {
+                let signature = format!("В{}", number);
+                storage.with_newspaper(Newspaper::new_unchecked(
+                    &signature,
+                    "Вестник",
+                    1990,
+                    None,
+                    [true; 7],
+                ))
+            })
+            .with_record("newspaper/В1050a", "{")
+            .with_record("newspaper/В2000", "{")
+            .with_record("audit/broken", "{");
+
+        // each one is counted by the chunk holding its key, the first one ends with В1099
+        let first = super::snapshot(&storage, None).unwrap();
+        assert_eq!(first.skipped, 2);
+        let second = super::snapshot(&storage, first.next_cursor.as_deref()).unwrap();
+        assert_eq!(second.skipped, 1);
+        assert_eq!(first.entries.len() + second.entries.len(), 154);
+    }
+
+    #[test]
+    fn windows() {
+        let windows: Vec<Option<String>> = super::windows("В109").collect();
+        assert_eq!(
+            windows,
+            vec![
+                Some("В10:".to_string()),
+                Some("В11".to_string()),
+                Some("В2".to_string()),
+                Some("Г".to_string()),
+                None
+            ]
+        );
+        assert_eq!(super::windows("").collect::<Vec<_>>(), vec![None]);
+        assert_eq!(super::after_prefix("a\u{10FFFF}").as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn audited_restore() {
+        let storage = MockHost::default().with_max_cards(30);
+        let chunk = super::snapshot(&storage, None).unwrap();
+
+        let mut staging = MockHost::default();
+        super::restore::<_, MockClock>(&mut staging, &MockClock::default(), chunk).unwrap();
+
+        let log = audit::log(&staging, 1, 10).unwrap();
+        assert_eq!(log.len(), 4);
+        let entry = serde_json::to_value(&log[0]).unwrap();
+        assert_eq!(entry["message"], "Restore");
+        assert_eq!(entry["record"], "config/max_cards");
+    }
+
+    #[test]
+    fn oversized() {
+        let entry = r#"{"namespace":"config","id":"max_cards","record":"40"}"#;
+        let json = format!(
+            r#"{{"version":1,"entries":[{}],"checksum":""}}"#,
+            vec![entry; 101].join(",")
+        );
+        let mut chunk: Chunk = serde_json::from_str(&json).unwrap();
+        chunk.checksum = super::checksum(&chunk.entries).unwrap();
+
+        assert!(matches!(
+            super::restore::<_, MockClock>(&mut MockHost::default(), &MockClock::default(), chunk),
+            Err(ServiceError::OversizedChunk(100))
+        ));
+    }
+
+    #[test]
+    fn corrupted() {
+        let storage = MockHost::default();
+        let mut chunk = super::snapshot(&storage, None).unwrap();
+        chunk.entries.pop();
+        assert!(matches!(
+            super::restore::<_, MockClock>(&mut MockHost::default(), &MockClock::default(), chunk),
+            Err(ServiceError::CorruptedSnapshot)
+        ));
+
+        let mut chunk = super::snapshot(&storage, None).unwrap();
+        chunk.version = 2;
+        assert!(matches!(
+            super::restore::<_, MockClock>(&mut MockHost::default(), &MockClock::default(), chunk),
+            Err(ServiceError::UnsupportedSnapshot(2))
+        ));
+    }
+
+    #[test]
+    fn unreadable_record() {
+        let json = r#"{"version":1,"entries":[{"namespace":"newspaper","id":"В1905","record":"{}"}],"checksum":"","next_cursor":null}"#;
+        let mut chunk: Chunk = serde_json::from_str(json).unwrap();
+        chunk.checksum = super::checksum(&chunk.entries).unwrap();
+
+        let mut storage = MockHost::default();
+        assert!(matches!(
+            super::restore::<_, MockClock>(&mut storage, &MockClock::default(), chunk),
+            Err(ServiceError::Storage(_))
+        ));
+        assert!(!NewspaperRepo::contains(&storage, "В1905"));
+    }
+
+    #[test]
+    fn misplaced_record() {
+        let newspaper = r#"{\"signature\":\"В1905\",\"name\":\"Поглед\",\"start_year\":1966,\"end_year\":1996,\"weekly_schedule\":[true,false,false,false,false,false,false]}"#;
+        for (namespace, id, record) in [
+            ("newspaper", "В1612", newspaper),
+            ("counter", "visits", "1"),
+            (
+                "order_number",
+                "DN-2024-000001-7",
+                r#"\"1732880395000_DN-2024-000002-5\""#,
+            ),
+        ] {
+            let json = format!(
+                r#"{{"version":1,"entries":[{{"namespace":"{}","id":"{}","record":"{}"}}],"checksum":""}}"#,
+                namespace, id, record
+            );
+            let mut chunk: Chunk = serde_json::from_str(&json).unwrap();
+            chunk.checksum = super::checksum(&chunk.entries).unwrap();
+
+            let mut storage = MockHost::default();
+            let key = format!("{}/{}", namespace, id);
+            let before = storage.retrieve(&key);
+            assert!(matches!(
+                super::restore::<_, MockClock>(&mut storage, &MockClock::default(), chunk),
+                Err(ServiceError::MisplacedRecord(_))
+            ));
+            assert_eq!(storage.retrieve(&key), before);
+        }
+    }
+}